@@ -1,175 +1,601 @@
 use crate::VERSION;
-use crate::config;
 use crate::domain::DomainResult;
+use crate::ndjson::LineDecoder;
+use crate::recording::{Recorder, Recording, RecordingHeader};
+use chrono::{DateTime, Local, TimeZone};
 use futures_core::stream::Stream;
 use futures_util::StreamExt;
-use reqwest::Client;
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
+use reqwest::{Client, StatusCode};
+use serde::Deserialize;
 use std::env::consts::{ARCH, OS};
 use std::error::Error;
+use std::fmt;
+use std::io;
 use std::pin::Pin;
+use std::time::{Duration, Instant};
 use tokio::sync::mpsc;
+use tracing::{debug, info, trace};
 
+/// TLDs checked when none are configured
+pub const DEFAULT_TLDS: &str = "com,dev,app";
+
+/// How long `check_health` waits for the server before giving up
+const HEALTH_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Longest response line accepted from the search stream; longer lines are skipped
+const MAX_LINE_LENGTH: usize = 64 * 1024;
+
+/// How long to wait for a connection to the API server when no timeout is configured
+pub const DEFAULT_CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// How long the server may stay silent when no idle timeout is configured
+pub const DEFAULT_IDLE_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Limits on how long a search may take. `None` means no limit.
+#[derive(Debug, Clone, Copy)]
+pub struct Timeouts {
+    /// Establishing the connection to the API server
+    pub connect: Option<Duration>,
+    /// The whole search, from sending the request to the last result
+    pub overall: Option<Duration>,
+    /// Waiting for the response, and then for each chunk of the stream after the last
+    pub idle: Option<Duration>,
+}
+
+impl Default for Timeouts {
+    fn default() -> Self {
+        Self {
+            connect: Some(DEFAULT_CONNECT_TIMEOUT),
+            overall: None,
+            idle: Some(DEFAULT_IDLE_TIMEOUT),
+        }
+    }
+}
+
+impl Timeouts {
+    /// How long to wait for the next response or chunk: the idle timeout, cut short by
+    /// whatever is left of the overall timeout
+    fn next_wait(&self, started: Instant) -> Option<Duration> {
+        let left = self
+            .overall
+            .map(|overall| overall.saturating_sub(started.elapsed()));
+        match (self.idle, left) {
+            (Some(idle), Some(left)) => Some(idle.min(left)),
+            (idle, left) => idle.or(left),
+        }
+    }
+
+    /// Explain a wait from `next_wait` running out. `results` is `None` while still waiting
+    /// for the response.
+    fn timed_out(&self, started: Instant, results: Option<u64>) -> TimeoutError {
+        match (self.overall, results) {
+            (Some(limit), results) if started.elapsed() >= limit => TimeoutError::Overall {
+                limit,
+                results: results.unwrap_or(0),
+            },
+            (_, None) => TimeoutError::NoResponse(self.idle.unwrap_or_default()),
+            (_, Some(results)) => TimeoutError::Stalled {
+                idle: self.idle.unwrap_or_default(),
+                results,
+            },
+        }
+    }
+}
+
+/// Why a search was cut short
+#[derive(Debug)]
+pub enum TimeoutError {
+    /// No connection to the server could be made
+    Connect(Duration),
+    /// The server accepted the connection but never sent a response
+    NoResponse(Duration),
+    /// The response started but then nothing arrived for the idle timeout
+    Stalled { idle: Duration, results: u64 },
+    /// The search was still running when the overall timeout ran out
+    Overall { limit: Duration, results: u64 },
+}
+
+impl fmt::Display for TimeoutError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TimeoutError::Connect(limit) => write!(
+                f,
+                "Could not connect to the API server within {}s (see --connect-timeout)",
+                limit.as_secs()
+            ),
+            TimeoutError::NoResponse(idle) => write!(
+                f,
+                "The API server never answered: no response after {}s (see --idle-timeout)",
+                idle.as_secs()
+            ),
+            TimeoutError::Stalled { idle, results } => write!(
+                f,
+                "The result stream stalled after {} results: nothing received for {}s (see --idle-timeout)",
+                results,
+                idle.as_secs()
+            ),
+            TimeoutError::Overall { limit, results } => write!(
+                f,
+                "The search did not finish within {}s, stopped after {} results (see --timeout)",
+                limit.as_secs(),
+                results
+            ),
+        }
+    }
+}
+
+impl Error for TimeoutError {}
+
+/// Wait for `future`, or return `None` if `limit` runs out first
+async fn within<F: Future>(limit: Option<Duration>, future: F) -> Option<F::Output> {
+    match limit {
+        Some(limit) => tokio::time::timeout(limit, future).await.ok(),
+        None => Some(future.await),
+    }
+}
+
+fn user_agent() -> String {
+    format!("NamekitCLI/{} ({}/{})", VERSION, OS, ARCH)
+}
+
+/// Header names and values, with credentials and session cookies replaced
+fn redacted_header_pairs(headers: &HeaderMap) -> impl Iterator<Item = (&str, &str)> {
+    headers.iter().map(|(name, value)| {
+        let value = if name == reqwest::header::AUTHORIZATION
+            || name == reqwest::header::COOKIE
+            || name == reqwest::header::SET_COOKIE
+        {
+            "<redacted>"
+        } else {
+            value.to_str().unwrap_or("<binary>")
+        };
+        (name.as_str(), value)
+    })
+}
+
+/// Headers as `name: value` pairs for logging, with credentials replaced
+fn redacted_headers(headers: &HeaderMap) -> Vec<String> {
+    redacted_header_pairs(headers)
+        .map(|(name, value)| format!("{}: {}", name, value))
+        .collect()
+}
+
+#[derive(Debug)]
+pub enum ApiError {
+    Unauthorized,
+    Status(StatusCode),
+    Request(reqwest::Error),
+}
+
+impl fmt::Display for ApiError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ApiError::Unauthorized => write!(f, "The API token was rejected"),
+            ApiError::Status(status) => write!(f, "API request failed with status: {}", status),
+            ApiError::Request(e) => {
+                write!(f, "Could not reach the API server: {}", e)?;
+                // The underlying cause, e.g. a TLS or proxy failure, is the useful part. Causes
+                // often repeat the message of the one before, so those are skipped.
+                let mut previous = e.to_string();
+                let mut source = e.source();
+                while let Some(cause) = source {
+                    let message = cause.to_string();
+                    if !previous.contains(&message) {
+                        write!(f, ": {}", message)?;
+                    }
+                    previous = message;
+                    source = cause.source();
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl From<reqwest::Error> for ApiError {
+    fn from(err: reqwest::Error) -> Self {
+        ApiError::Request(err)
+    }
+}
+
+impl Error for ApiError {}
+
+/// The account a token belongs to, as returned by the account endpoint
+#[derive(Debug, Deserialize)]
+pub struct Account {
+    #[serde(default)]
+    pub email: Option<String>,
+    #[serde(default)]
+    pub plan: Option<String>,
+    #[serde(default)]
+    pub usage: Option<Usage>,
+}
+
+/// Query allowance for the current billing period
+#[derive(Debug, Deserialize)]
+pub struct Usage {
+    #[serde(default)]
+    pub limit: Option<u64>,
+    #[serde(default)]
+    pub remaining: Option<u64>,
+    /// When the allowance resets, as an RFC 3339 timestamp or Unix time
+    #[serde(default)]
+    pub reset: Option<serde_json::Value>,
+}
+
+impl Usage {
+    pub fn reset_time(&self) -> Option<DateTime<Local>> {
+        match self.reset.as_ref()? {
+            serde_json::Value::String(reset) => parse_reset(reset),
+            serde_json::Value::Number(reset) => Local.timestamp_opt(reset.as_i64()?, 0).single(),
+            _ => None,
+        }
+    }
+}
+
+/// Warn once fewer than this fraction of the allowance is left
+const LOW_QUOTA_FRACTION: f64 = 0.1;
+
+/// Rate limit state reported in the headers of a search response
+#[derive(Debug, Default)]
+pub struct RateLimit {
+    pub limit: Option<u64>,
+    pub remaining: Option<u64>,
+    pub reset: Option<DateTime<Local>>,
+}
+
+impl RateLimit {
+    /// Read the `X-RateLimit-*` headers, or the unprefixed `RateLimit-*` ones, falling
+    /// back to `Retry-After` for the reset time
+    pub fn from_headers(headers: &HeaderMap) -> Self {
+        let header = |name: &str| {
+            headers
+                .get(format!("x-ratelimit-{}", name))
+                .or_else(|| headers.get(format!("ratelimit-{}", name)))
+                .and_then(|value| value.to_str().ok())
+                .map(str::trim)
+        };
+
+        Self {
+            limit: header("limit").and_then(|v| v.parse().ok()),
+            remaining: header("remaining").and_then(|v| v.parse().ok()),
+            reset: header("reset")
+                .or_else(|| headers.get("retry-after").and_then(|v| v.to_str().ok()))
+                .and_then(parse_reset),
+        }
+    }
+
+    /// Whether the remaining allowance is low enough to warn about
+    pub fn is_low(&self) -> bool {
+        match (self.limit, self.remaining) {
+            (Some(limit), Some(remaining)) if limit > 0 => {
+                (remaining as f64) <= limit as f64 * LOW_QUOTA_FRACTION
+            }
+            _ => false,
+        }
+    }
+}
+
+/// Parse a reset time given as an RFC 3339 timestamp, a Unix time, or a number of
+/// seconds from now (small numbers can't be a Unix time in the past decades)
+pub fn parse_reset(value: &str) -> Option<DateTime<Local>> {
+    if let Ok(time) = DateTime::parse_from_rfc3339(value) {
+        return Some(time.with_timezone(&Local));
+    }
+
+    let seconds: i64 = value.parse().ok()?;
+    if seconds > 1_000_000_000 {
+        Local.timestamp_opt(seconds, 0).single()
+    } else {
+        Some(Local::now() + chrono::Duration::seconds(seconds))
+    }
+}
+
+/// A reset time for display, e.g. `2026-11-01 09:00 (in 3h 20m)`
+pub fn describe_reset(reset: DateTime<Local>) -> String {
+    let remaining = reset - Local::now();
+    let minutes = remaining.num_minutes().max(0);
+    let from_now = match (minutes / (24 * 60), minutes / 60 % 24, minutes % 60) {
+        (0, 0, m) => format!("{}m", m),
+        (0, h, m) => format!("{}h {}m", h, m),
+        (d, h, _) => format!("{}d {}h", d, h),
+    };
+    format!("{} (in {})", reset.format("%Y-%m-%d %H:%M"), from_now)
+}
+
+/// Look up the account `token` belongs to, which also verifies the token
+pub async fn fetch_account(
+    client: &Client,
+    api_server: &str,
+    token: &str,
+) -> Result<Account, ApiError> {
+    let endpoint = format!("{}/account", api_server);
+    info!(%endpoint, "fetching account");
+    let started = Instant::now();
+
+    let response = client
+        .get(&endpoint)
+        .header("User-Agent", user_agent())
+        .header("Authorization", format!("Bearer {}", token))
+        .send()
+        .await?;
+
+    info!(status = %response.status(), elapsed_ms = started.elapsed().as_millis() as u64, "account response");
+    debug!(headers = ?redacted_headers(response.headers()), "response headers");
+
+    match response.status() {
+        status if status.is_success() => Ok(response.json().await?),
+        StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN => Err(ApiError::Unauthorized),
+        status => Err(ApiError::Status(status)),
+    }
+}
+
+/// Check that `api_server` answers on its health endpoint
+pub async fn check_health(client: &Client, api_server: &str) -> Result<(), Box<dyn Error>> {
+    let endpoint = format!("{}/health", api_server);
+    info!(%endpoint, "checking server health");
+
+    let response = client
+        .get(&endpoint)
+        .header("User-Agent", user_agent())
+        .timeout(HEALTH_TIMEOUT)
+        .send()
+        .await?;
+    info!(status = %response.status(), "health response");
+
+    if !response.status().is_success() {
+        return Err(format!("health check returned status {}", response.status()).into());
+    }
+    Ok(())
+}
+
+/// Tell the user why a search request failed
+fn report_failure(status: StatusCode, rate_limit: &RateLimit) {
+    // Handle rate limiting (429 Too Many Requests) specifically
+    if status == StatusCode::TOO_MANY_REQUESTS {
+        eprintln!(
+            "You've reached the limit of the free tier. Please visit https://namekit.app to upgrade your plan."
+        );
+        if let Some(reset) = rate_limit.reset {
+            eprintln!("Your query allowance resets at {}", describe_reset(reset));
+        }
+    } else {
+        eprintln!("API request failed with status: {}", status);
+    }
+}
+
+/// Turn one line of the response into a result. Lines that aren't domain results are
+/// reported and skipped.
+fn parse_line(line: &str) -> Option<DomainResult> {
+    let json = match serde_json::from_str::<serde_json::Value>(line) {
+        Ok(json) => json,
+        Err(e) => {
+            debug!(line, error = %e, "could not parse line");
+            eprintln!("Error parsing JSON line: {}", e);
+            return None;
+        }
+    };
+
+    // Extract domain information from the JSON
+    let Some(domain) = json.get("domain").and_then(|d| d.as_str()) else {
+        debug!(%json, "ignoring line without a domain");
+        eprintln!("Ignoring unexpected JSON: {}", json);
+        return None;
+    };
+    let available = json.get("available").and_then(|a| a.as_bool());
+    let premium = json
+        .get("premium")
+        .and_then(|p| p.as_bool())
+        .unwrap_or(false);
+    let price = json.get("price").and_then(|p| match p {
+        serde_json::Value::String(price) => Some(price.clone()),
+        serde_json::Value::Number(price) => Some(price.to_string()),
+        _ => None,
+    });
+
+    debug!(domain, ?available, premium, ?price, "parsed result");
+    Some(DomainResult {
+        price,
+        ..DomainResult::new_with_premium(domain.to_string(), available, premium)
+    })
+}
+
+/// Run `write` against the recorder, giving up on recording (but not the search) if it fails
+fn record(recorder: &mut Option<Recorder>, write: impl FnOnce(&mut Recorder) -> io::Result<()>) {
+    if let Some(Err(e)) = recorder.as_mut().map(write) {
+        eprintln!("Stopped recording: {}", e);
+        *recorder = None;
+    }
+}
+
+/// What a search asks the API for
+#[derive(Debug, Clone)]
+pub struct SearchRequest {
+    pub query: String,
+    /// `ai` or `tld`
+    pub mode: String,
+    /// Comma separated TLDs to check
+    pub tlds: String,
+}
+
+/// Search the API, streaming results as they arrive. When `recorder` is given, the raw
+/// response is saved to it for `replay_domains`. If a limit in `timeouts` runs out, the
+/// stream ends early after explaining why. The connect timeout is applied by `client`.
 pub async fn stream_domains(
-    query: &str,
-    mode: &str,
+    client: &Client,
+    request: SearchRequest,
     token: &str,
+    api_server: &str,
+    timeouts: Timeouts,
+    mut recorder: Option<Recorder>,
 ) -> Result<Pin<Box<dyn Stream<Item = DomainResult> + Send>>, Box<dyn Error>> {
     // Create a channel for sending domains as they're processed
     let (tx, rx) = mpsc::channel(32);
 
     // Clone values for the spawned task
-    let query = query.to_string();
-    let mode = mode.to_string();
+    let SearchRequest { query, mode, tlds } = request;
     let token = token.to_string();
+    let api_server = api_server.to_string();
+    let client = client.clone();
 
     // Spawn a task to process the API response
     tokio::spawn(async move {
-        let client = Client::new();
-
-        // Load config to get the API server URL
-        if let Ok(config) = config::Config::load() {
-            let api_server = config.get_api_server();
-
-            // Create the endpoint URL
-            let endpoint = format!("{}/domains/stream", api_server);
-
-            // Create the request body with the query parameter
-            let body = serde_json::json!({
-                "q": query,
-                "mode": mode,
-                "tlds": "com,dev,app",
-            });
-
-            let user_agent = format!("NamekitCLI/{} ({}/{})", VERSION, OS, ARCH);
-
-            // Make the POST request to the API with the token from config
-            match client
-                .post(&endpoint)
-                .header("User-Agent", user_agent)
-                .header("Authorization", format!("Bearer {}", token))
-                .json(&body)
-                .send()
-                .await
-            {
-                Ok(response) => {
-                    // Check if the request was successful
-                    if !response.status().is_success() {
-                        // Handle rate limiting (429 Too Many Requests) specifically
-                        if response.status() == reqwest::StatusCode::TOO_MANY_REQUESTS {
-                            eprintln!(
-                                "You've reached the limit of the free tier. Please visit https://namekit.app to upgrade your plan."
+        // Create the endpoint URL
+        let endpoint = format!("{}/domains/stream", api_server);
+
+        // Create the request body with the query parameter
+        let body = serde_json::json!({
+            "q": query,
+            "mode": mode,
+            "tlds": tlds,
+        });
+
+        // Make the POST request to the API with the token from config
+        let request = match client
+            .post(&endpoint)
+            .header("User-Agent", user_agent())
+            .header("Authorization", format!("Bearer {}", token))
+            .json(&body)
+            .build()
+        {
+            Ok(request) => request,
+            Err(e) => {
+                eprintln!("Error building request: {}", e);
+                return;
+            }
+        };
+
+        info!(%endpoint, %mode, %tlds, "sending search request");
+        debug!(%body, "request body");
+        debug!(headers = ?redacted_headers(request.headers()), "request headers");
+        let started = Instant::now();
+        let elapsed_ms = || started.elapsed().as_millis() as u64;
+        let mut lines = 0u64;
+        let mut results = 0u64;
+
+        match within(timeouts.next_wait(started), client.execute(request)).await {
+            Some(Ok(response)) => {
+                info!(status = %response.status(), elapsed_ms = elapsed_ms(), "response received");
+                debug!(headers = ?redacted_headers(response.headers()), "response headers");
+                let rate_limit = RateLimit::from_headers(response.headers());
+
+                record(&mut recorder, |recorder| {
+                    let mut header = RecordingHeader::new(
+                        &endpoint,
+                        &query,
+                        &mode,
+                        &tlds,
+                        response.status().as_u16(),
+                    );
+                    header.headers = redacted_header_pairs(response.headers())
+                        .map(|(name, value)| (name.to_string(), value.to_string()))
+                        .collect();
+                    recorder.write_header(&header)
+                });
+
+                // Check if the request was successful
+                if !response.status().is_success() {
+                    report_failure(response.status(), &rate_limit);
+                    return;
+                }
+
+                if rate_limit.is_low() {
+                    let remaining = rate_limit.remaining.unwrap_or_default();
+                    let limit = rate_limit.limit.unwrap_or_default();
+                    match rate_limit.reset {
+                        Some(reset) => eprintln!(
+                            "Warning: {} of {} queries left, resetting at {}",
+                            remaining,
+                            limit,
+                            describe_reset(reset)
+                        ),
+                        None => eprintln!("Warning: {} of {} queries left", remaining, limit),
+                    }
+                }
+
+                // Process the streaming response one complete line at a time
+                let mut decoder = LineDecoder::new(MAX_LINE_LENGTH);
+                let mut stream = response.bytes_stream();
+
+                loop {
+                    let next = tokio::select! {
+                        next = within(timeouts.next_wait(started), stream.next()) => next,
+                        // Nobody is reading the results any more, e.g. after Ctrl-C
+                        _ = tx.closed() => {
+                            info!(lines, results, elapsed_ms = elapsed_ms(), "search cancelled");
+                            return;
+                        }
+                    };
+                    let (decoded, done) = match next {
+                        Some(Some(Ok(chunk))) => {
+                            record(&mut recorder, |recorder| recorder.write_chunk(&chunk));
+                            (decoder.push(&chunk), false)
+                        }
+                        Some(Some(Err(e))) => {
+                            debug!(error = %e, lines, results, elapsed_ms = elapsed_ms(), "stream failed");
+                            eprintln!("Error reading chunk: {}", e);
+                            (decoder.finish().into_iter().collect(), true)
+                        }
+                        Some(None) => (decoder.finish().into_iter().collect(), true),
+                        // A partial line left in the decoder is dropped: the rest never came
+                        None => {
+                            let error = timeouts.timed_out(started, Some(results));
+                            debug!(
+                                ?error,
+                                lines,
+                                results,
+                                elapsed_ms = elapsed_ms(),
+                                "search timed out"
                             );
-                        } else {
-                            eprintln!("API request failed with status: {}", response.status());
+                            eprintln!("{}", error);
+                            (Vec::new(), true)
                         }
-                        return;
-                    }
+                    };
 
-                    // Process the streaming response
-                    let mut buffer = String::new();
-                    let mut stream = response.bytes_stream();
-
-                    while let Some(chunk_result) = stream.next().await {
-                        match chunk_result {
-                            Ok(chunk) => {
-                                // Add the new chunk to our buffer
-                                buffer.push_str(&String::from_utf8_lossy(&chunk));
-
-                                // Process complete lines by splitting on newlines
-                                let parts: Vec<&str> = buffer.split('\n').collect();
-
-                                // If we have multiple parts, process all but the last one
-                                if parts.len() > 1 {
-                                    // Process all complete lines (all but the last part)
-                                    for line in &parts[0..parts.len() - 1] {
-                                        if line.trim().is_empty() {
-                                            continue;
-                                        }
-
-                                        // Parse the JSON line
-                                        match serde_json::from_str::<serde_json::Value>(line) {
-                                            Ok(json) => {
-                                                // Extract domain information from the JSON
-                                                if let Some(domain) =
-                                                    json.get("domain").and_then(|d| d.as_str())
-                                                {
-                                                    let available = json
-                                                        .get("available")
-                                                        .and_then(|a| a.as_bool())
-                                                        .unwrap_or(false);
-                                                    let premium = json
-                                                        .get("premium")
-                                                        .and_then(|p| p.as_bool())
-                                                        .unwrap_or(false);
-
-                                                    // Send the domain through the channel
-                                                    let domain_result =
-                                                        DomainResult::new_with_premium(
-                                                            domain.to_string(),
-                                                            available,
-                                                            premium,
-                                                        );
-
-                                                    if tx.send(domain_result).await.is_err() {
-                                                        // Channel closed, receiver dropped
-                                                        return;
-                                                    }
-                                                } else {
-                                                    println!("Ignoring unexpected JSON: {}", json);
-                                                }
-                                            }
-                                            Err(e) => {
-                                                eprintln!("Error parsing JSON line: {}", e);
-                                            }
-                                        }
-                                    }
-
-                                    // Keep only the last part in the buffer
-                                    buffer = parts[parts.len() - 1].to_string();
-                                }
-                            }
+                    for line in decoded {
+                        let line = match line {
+                            Ok(line) => line,
                             Err(e) => {
-                                eprintln!("Error reading chunk: {}", e);
-                                break;
+                                debug!(error = %e, elapsed_ms = elapsed_ms(), "skipped line");
+                                eprintln!("Skipping response line: {}", e);
+                                continue;
                             }
-                        }
-                    }
+                        };
+                        lines += 1;
+                        trace!(line, elapsed_ms = elapsed_ms(), "received line");
 
-                    // Process any remaining data in the buffer
-                    if !buffer.trim().is_empty() {
-                        match serde_json::from_str::<serde_json::Value>(&buffer) {
-                            Ok(json) => {
-                                if let Some(domain) = json.get("domain").and_then(|d| d.as_str()) {
-                                    let available = json
-                                        .get("available")
-                                        .and_then(|a| a.as_bool())
-                                        .unwrap_or(false);
-                                    let premium = json
-                                        .get("premium")
-                                        .and_then(|p| p.as_bool())
-                                        .unwrap_or(false);
-
-                                    let domain_result = DomainResult::new_with_premium(
-                                        domain.to_string(),
-                                        available,
-                                        premium,
-                                    );
-
-                                    let _ = tx.send(domain_result).await;
-                                }
-                            }
-                            Err(e) => {
-                                eprintln!("Error parsing final JSON: {}", e);
+                        if let Some(mut domain_result) = parse_line(&line) {
+                            results += 1;
+                            domain_result.query = Some(query.clone());
+
+                            // Send the domain through the channel
+                            if tx.send(domain_result).await.is_err() {
+                                // Channel closed, receiver dropped
+                                return;
                             }
                         }
                     }
+
+                    if done {
+                        break;
+                    }
                 }
-                Err(e) => {
-                    eprintln!("Error sending request: {}", e);
+
+                info!(lines, results, elapsed_ms = elapsed_ms(), "stream finished");
+            }
+            Some(Err(e)) => {
+                debug!(error = ?e, elapsed_ms = elapsed_ms(), "request failed");
+                match timeouts.connect {
+                    Some(limit) if e.is_timeout() => eprintln!("{}", TimeoutError::Connect(limit)),
+                    _ => eprintln!("{}", ApiError::Request(e)),
                 }
             }
-        } else {
-            eprintln!("Error loading config");
+            None => {
+                let error = timeouts.timed_out(started, None);
+                debug!(?error, elapsed_ms = elapsed_ms(), "search timed out");
+                eprintln!("{}", error);
+            }
         }
 
         // Channel will be closed when tx is dropped at the end of this function
@@ -180,3 +606,202 @@ pub async fn stream_domains(
 
     Ok(Box::pin(stream))
 }
+
+/// Feed a recorded response through the same parsing as a live search, without any
+/// network access
+pub fn replay_domains(recording: Recording) -> Pin<Box<dyn Stream<Item = DomainResult> + Send>> {
+    let header = &recording.header;
+    info!(
+        endpoint = %header.endpoint,
+        query = %header.query,
+        recorded_at = %header.recorded_at,
+        bytes = recording.body.len(),
+        "replaying recording"
+    );
+
+    let status = StatusCode::from_u16(header.status).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR);
+    if !status.is_success() {
+        let headers: HeaderMap = header
+            .headers
+            .iter()
+            .filter_map(|(name, value)| {
+                Some((
+                    HeaderName::from_bytes(name.as_bytes()).ok()?,
+                    HeaderValue::from_str(value).ok()?,
+                ))
+            })
+            .collect();
+        report_failure(status, &RateLimit::from_headers(&headers));
+        return Box::pin(futures_util::stream::empty());
+    }
+
+    // Split the body with the same decoder as a live search, so lines it rejected then
+    // are skipped with the same warning now
+    let mut decoder = LineDecoder::new(MAX_LINE_LENGTH);
+    let mut lines = decoder.push(&recording.body);
+    lines.extend(decoder.finish());
+
+    let query = header.query.clone();
+    let results = lines
+        .into_iter()
+        .filter_map(|line| match line {
+            Ok(line) => Some(line),
+            Err(e) => {
+                debug!(error = %e, "skipped line");
+                eprintln!("Skipping response line: {}", e);
+                None
+            }
+        })
+        .filter_map(|line| parse_line(&line))
+        .map(move |mut result| {
+            result.query = Some(query.clone());
+            result
+        });
+    Box::pin(futures_util::stream::iter(results))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::DomainStatus;
+    use std::path::Path;
+
+    fn fixture(name: &str) -> Recording {
+        let path = format!("{}/tests/fixtures/{}", env!("CARGO_MANIFEST_DIR"), name);
+        Recording::read(Path::new(&path)).expect("fixture should be a valid recording")
+    }
+
+    async fn replay(name: &str) -> Vec<DomainResult> {
+        replay_domains(fixture(name)).collect().await
+    }
+
+    #[tokio::test]
+    async fn replays_every_result_in_order() {
+        let results = replay("search_basic.ndjson").await;
+
+        let names: Vec<&str> = results.iter().map(|r| r.name.as_str()).collect();
+        assert_eq!(names, ["foo.com", "foo.dev", "foo.app", "foo.io"]);
+
+        let statuses: Vec<DomainStatus> = results.iter().map(|r| r.status()).collect();
+        assert_eq!(
+            statuses,
+            [
+                DomainStatus::Available,
+                DomainStatus::Taken,
+                DomainStatus::Premium,
+                DomainStatus::Unknown
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn skips_blank_malformed_and_unexpected_lines() {
+        let results = replay("search_malformed.ndjson").await;
+
+        let names: Vec<&str> = results.iter().map(|r| r.name.as_str()).collect();
+        assert_eq!(names, ["brewhaus.com", "roastery.app", "cafecito.com"]);
+        assert!(!results[0].premium, "premium defaults to false");
+    }
+
+    #[tokio::test]
+    async fn skips_lines_that_are_not_utf8() {
+        let recording = fixture("search_invalid_line.ndjson");
+        assert!(
+            recording.body.windows(3).any(|w| w == b"caf"),
+            "the rejected line is kept in the recording"
+        );
+
+        let results: Vec<DomainResult> = replay_domains(recording).collect().await;
+        let names: Vec<&str> = results.iter().map(|r| r.name.as_str()).collect();
+        assert_eq!(names, ["teabar.com", "leafy.app"]);
+    }
+
+    #[test]
+    fn keeps_the_price_as_sent() {
+        let price = |line: &str| parse_line(line).unwrap().price;
+        assert_eq!(
+            price(r#"{"domain": "foo.com", "price": 12.99}"#).as_deref(),
+            Some("12.99")
+        );
+        assert_eq!(
+            price(r#"{"domain": "foo.ai", "price": "$79.00"}"#).as_deref(),
+            Some("$79.00")
+        );
+        assert_eq!(price(r#"{"domain": "foo.dev", "price": null}"#), None);
+        assert_eq!(price(r#"{"domain": "foo.app"}"#), None);
+    }
+
+    #[tokio::test]
+    async fn failed_response_yields_no_results() {
+        assert!(replay("rate_limited.ndjson").await.is_empty());
+    }
+
+    #[test]
+    fn parses_rate_limit_headers() {
+        let mut headers = HeaderMap::new();
+        headers.insert("x-ratelimit-limit", HeaderValue::from_static("100"));
+        headers.insert("x-ratelimit-remaining", HeaderValue::from_static("10"));
+        headers.insert("x-ratelimit-reset", HeaderValue::from_static("1800000000"));
+
+        let rate_limit = RateLimit::from_headers(&headers);
+        assert_eq!(rate_limit.limit, Some(100));
+        assert_eq!(rate_limit.remaining, Some(10));
+        assert_eq!(rate_limit.reset.map(|r| r.timestamp()), Some(1_800_000_000));
+        assert!(rate_limit.is_low());
+
+        headers.insert("x-ratelimit-remaining", HeaderValue::from_static("11"));
+        assert!(!RateLimit::from_headers(&headers).is_low());
+    }
+
+    #[test]
+    fn parses_reset_times() {
+        let rfc3339 = parse_reset("2026-11-01T00:00:00Z").unwrap();
+        assert_eq!(rfc3339.timestamp(), 1_793_491_200);
+
+        let relative = parse_reset("60").unwrap() - Local::now();
+        assert!((55..=60).contains(&relative.num_seconds()));
+
+        assert!(parse_reset("soon").is_none());
+    }
+
+    #[test]
+    fn redacts_credentials_from_logged_headers() {
+        let mut headers = HeaderMap::new();
+        headers.insert("authorization", HeaderValue::from_static("Bearer secret"));
+        headers.insert("set-cookie", HeaderValue::from_static("session=secret"));
+        headers.insert("user-agent", HeaderValue::from_static("NamekitCLI"));
+
+        let logged = redacted_headers(&headers).join("\n");
+        assert!(!logged.contains("secret"));
+        assert!(logged.contains("authorization: <redacted>"));
+        assert!(logged.contains("set-cookie: <redacted>"));
+        assert!(logged.contains("user-agent: NamekitCLI"));
+    }
+
+    #[test]
+    fn timeouts_tell_a_silent_server_from_a_stalled_stream() {
+        let timeouts = Timeouts {
+            connect: None,
+            overall: Some(Duration::from_secs(60)),
+            idle: Some(Duration::from_secs(5)),
+        };
+        let started = Instant::now();
+        assert_eq!(timeouts.next_wait(started), Some(Duration::from_secs(5)));
+        assert!(matches!(
+            timeouts.timed_out(started, None),
+            TimeoutError::NoResponse(_)
+        ));
+        assert!(matches!(
+            timeouts.timed_out(started, Some(12)),
+            TimeoutError::Stalled { results: 12, .. }
+        ));
+
+        // Once the overall limit has passed, that is what gets reported
+        let started = Instant::now() - Duration::from_secs(61);
+        assert_eq!(timeouts.next_wait(started), Some(Duration::ZERO));
+        assert!(matches!(
+            timeouts.timed_out(started, Some(3)),
+            TimeoutError::Overall { results: 3, .. }
+        ));
+    }
+}
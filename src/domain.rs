@@ -3,24 +3,60 @@ use serde::{Deserialize, Serialize};
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct DomainResult {
     pub name: String,
-    pub available: bool,
+    /// Whether the domain can be registered, or `None` when the API did not say
+    pub available: Option<bool>,
     pub premium: bool,
+    /// Registration price as the API wrote it, e.g. `12.99`, or `None` when it sent none
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub price: Option<String>,
+    /// The search query that found this domain
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub query: Option<String>,
+}
+
+/// The state of a domain as presented to the user
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DomainStatus {
+    Available,
+    Taken,
+    Premium,
+    Unknown,
 }
 
 impl DomainResult {
     pub fn _new(name: String, available: bool) -> Self {
         Self {
             name,
-            available,
+            available: Some(available),
             premium: false,
+            price: None,
+            query: None,
         }
     }
 
-    pub fn new_with_premium(name: String, available: bool, premium: bool) -> Self {
+    pub fn new_with_premium(name: String, available: Option<bool>, premium: bool) -> Self {
         Self {
             name,
             available,
             premium,
+            price: None,
+            query: None,
+        }
+    }
+
+    pub fn is_available(&self) -> bool {
+        self.available == Some(true)
+    }
+
+    pub fn status(&self) -> DomainStatus {
+        if self.premium {
+            DomainStatus::Premium
+        } else {
+            match self.available {
+                Some(true) => DomainStatus::Available,
+                Some(false) => DomainStatus::Taken,
+                None => DomainStatus::Unknown,
+            }
         }
     }
 }
@@ -1,33 +1,133 @@
-use clap::{Parser, Subcommand};
+use clap::{CommandFactory, Parser, Subcommand};
+use domain::DomainResult;
+use futures_core::stream::Stream;
 use futures_util::StreamExt;
-use output::{OutputMode, display_results};
+use output::{ColorChoice, Filter, Footer, OutputMode, SortOrder, display_results};
+use std::fs::File;
+use std::io::{self, BufWriter, IsTerminal};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use template::Template;
+use theme::Theme;
+use zeroize::Zeroizing;
 
 mod api;
+mod client;
 mod config;
 mod domain;
+mod ndjson;
 mod output;
+mod recording;
+mod settings;
+mod template;
+mod theme;
+mod vault;
 
 const VERSION: &str = env!("CARGO_PKG_VERSION");
 
+/// Exit code after Ctrl-C, following the shell convention of 128 + SIGINT
+const EXIT_INTERRUPTED: i32 = 130;
+
 #[derive(Parser)]
 #[command(name = "namekit")]
 #[command(version = VERSION)]
 #[command(about = "A command line toolkit for quickly exploring domain names available for registration", long_about = None)]
+#[command(arg_required_else_help = true)]
 struct Cli {
-    /// Output format: 'list' for single line, 'grid' for terminal-width grid, or 'json' for JSON array output
-    #[arg(short, long, default_value = "grid")]
-    output: String,
+    /// Output format: 'list' for single line, 'grid' for terminal-width grid, 'json' for JSON array output, 'markdown' for a Markdown table or 'html' for an HTML report [default: grid]
+    #[arg(short, long)]
+    output: Option<String>,
 
-    /// Show taken domains (by default only available domains are shown)
+    /// Print each domain on its own line using a template, e.g. '{name}\t{tld}\t{available?yes:no}' (implies list output)
     #[arg(long)]
+    format: Option<String>,
+
+    /// Also write results to a file, in a format inferred from its extension (.json, .md, .html or .txt)
+    #[arg(long, value_name = "PATH")]
+    out: Option<PathBuf>,
+
+    /// Save the raw API response of a search, with the request details, for --replay
+    #[arg(long, value_name = "PATH")]
+    record: Option<PathBuf>,
+
+    /// Show the results from a --record file instead of running a search (no network access)
+    #[arg(long, value_name = "PATH")]
+    replay: Option<PathBuf>,
+
+    /// Show taken domains (by default only available domains are shown)
+    #[arg(long, overrides_with = "no_show_taken")]
     show_taken: bool,
 
-    /// Hide premium domains (by default premium domains are shown)
+    /// Only show available domains, even if show_taken is set in the config
     #[arg(long)]
+    no_show_taken: bool,
+
+    /// Hide premium domains (by default premium domains are shown)
+    #[arg(long, overrides_with = "no_hide_premium")]
     hide_premium: bool,
 
+    /// Show premium domains, even if hide_premium is set in the config
+    #[arg(long)]
+    no_hide_premium: bool,
+
+    /// Comma separated TLDs to check, e.g. 'com,io,ai' [default: com,dev,app]
+    #[arg(long)]
+    tlds: Option<String>,
+
+    /// Order of results: 'none' to show them as they arrive, or 'name', 'tld' or 'status' (waits for the search to finish) [default: none]
+    #[arg(long, value_parser = output::SORT_ORDERS)]
+    sort: Option<String>,
+
+    /// With several queries, group grid and list output under a heading for each query (waits for every search to finish)
+    #[arg(long)]
+    sections: bool,
+
+    /// Log requests and responses to stderr; repeat (-vv) to include headers and each result
+    #[arg(short, long, action = clap::ArgAction::Count)]
+    verbose: u8,
+
+    /// Log everything, including the raw response lines and HTTP library internals
+    #[arg(long)]
+    debug: bool,
+
+    /// Don't print the summary line after the results
+    #[arg(short, long)]
+    quiet: bool,
+
+    /// When to colour output: 'auto' (honours NO_COLOR, CLICOLOR_FORCE and terminal detection), 'always' or 'never'
+    #[arg(long, default_value = "auto", value_parser = ["auto", "always", "never"])]
+    color: String,
+
+    /// Colour theme preset: 'default', 'colorblind' or 'monochrome' (overrides the theme in the config file)
+    #[arg(long, value_parser = theme::PRESETS)]
+    theme: Option<String>,
+
+    /// Configuration profile to use instead of the current one
+    #[arg(long)]
+    profile: Option<String>,
+
+    /// API token to use instead of the configured one (or set NAMEKIT_TOKEN)
+    #[arg(long)]
+    token: Option<String>,
+
+    /// API server to use instead of the configured one (or set NAMEKIT_API_SERVER)
+    #[arg(long)]
+    api_server: Option<String>,
+
+    /// Seconds to wait for a connection to the API server, or 0 for no limit [default: 10]
+    #[arg(long, value_name = "SECS")]
+    connect_timeout: Option<u64>,
+
+    /// Seconds a whole search may take, or 0 for no limit [default: no limit]
+    #[arg(long, value_name = "SECS")]
+    timeout: Option<u64>,
+
+    /// Seconds to wait for the server to answer, and then for each further result, or 0 for no limit [default: 30]
+    #[arg(long, value_name = "SECS")]
+    idle_timeout: Option<u64>,
+
     #[command(subcommand)]
-    command: Commands,
+    command: Option<Commands>,
 }
 
 #[derive(Subcommand)]
@@ -43,22 +143,40 @@ enum Commands {
         #[command(subcommand)]
         action: ConfigCommands,
     },
+
+    /// Prompt for an API token, check it with the API and save it
+    Login {
+        /// Store the token in the encrypted credential file instead of the config file
+        #[arg(long)]
+        encrypt: bool,
+    },
+
+    /// Remove the saved API token
+    Logout,
+
+    /// Show your plan, remaining queries and when the allowance resets
+    Usage,
 }
 
 #[derive(Subcommand)]
 enum SearchMode {
     /// Search for domains using AI-powered suggestions
     AI {
-        /// Terms to use for domain search
+        /// Terms to use for domain search; quoted prompts such as "coffee shop" "tea bar"
+        /// are searched separately
         #[arg(required = true)]
         terms: Vec<String>,
+
+        /// Search each term as a prompt of its own, even single words, e.g. --each tea coffee
+        #[arg(long)]
+        each: bool,
     },
 
     /// Search for a specific domain name with different TLDs
     Tld {
-        /// Domain name to check with different TLDs
+        /// Domain names to check with different TLDs, each searched separately
         #[arg(required = true)]
-        query: String,
+        names: Vec<String>,
     },
 }
 
@@ -66,141 +184,855 @@ enum SearchMode {
 enum ConfigCommands {
     /// Set the API token for accessing the domain API
     SetToken {
-        /// The API token to set
-        token: String,
+        /// The API token to set; prompted for when left out, which keeps it out of the shell history
+        token: Option<String>,
+
+        /// Store the token in an encrypted credential file, unlocked with a passphrase
+        /// (prompted for, or read from NAMEKIT_PASSPHRASE)
+        #[arg(long)]
+        encrypt: bool,
     },
 
     /// Set the API server URL
     SetApiServer {
-        /// The API server URL to use (default: https://api.namedrop.dev)
+        /// The API server URL to use (default: https://api.namekit.app)
         server: String,
+
+        /// Check that the server answers on its health endpoint before saving it
+        #[arg(long)]
+        check: bool,
+    },
+
+    /// Switch the current profile, creating it if it doesn't exist
+    Use {
+        /// Name of the profile to use
+        profile: String,
+    },
+
+    /// Print the value of a setting
+    #[command(after_help = settings_help())]
+    Get {
+        /// Name of the setting, e.g. 'api_server' or 'theme.premium.color'
+        key: String,
+    },
+
+    /// Change a setting
+    #[command(after_help = settings_help())]
+    Set {
+        /// Name of the setting, e.g. 'api_server' or 'theme.premium.color'
+        key: String,
+        /// New value for the setting
+        value: String,
+    },
+
+    /// Remove a setting so its default applies
+    #[command(after_help = settings_help())]
+    Unset {
+        /// Name of the setting, e.g. 'api_server' or 'theme.premium.color'
+        key: String,
+    },
+
+    /// Open the config file in $VISUAL or $EDITOR, checking it is valid before saving
+    Edit,
+
+    /// Upgrade the config file to the current schema version, optionally converting its format
+    Migrate {
+        /// Format to convert the config file to; the original is kept with a .bak suffix
+        #[arg(long, value_parser = config::CONFIG_FORMATS)]
+        to: Option<String>,
     },
 
     /// Show the current configuration
     Show,
 }
 
+/// List every setting for the help of `config get/set/unset`
+fn settings_help() -> String {
+    let mut help = String::from("Settings:\n");
+    for setting in settings::all() {
+        help.push_str(&format!("  {:<26} {}\n", setting.key, setting.description));
+    }
+    help
+}
+
+/// Load the config file, applying the profile, token and server given on the command line
+fn load_config(cli: &Cli) -> Result<config::Config, config::ConfigError> {
+    let config = config::Config::load(&config::Overrides {
+        profile: cli.profile.clone(),
+        token: cli.token.clone(),
+        api_server: cli.api_server.clone(),
+    });
+
+    match &config {
+        // A malformed --api-server or NAMEKIT_API_SERVER is a usage error, not a config problem
+        Err(e @ config::ConfigError::InvalidApiServer(..)) => {
+            eprintln!("{}", e);
+            std::process::exit(2);
+        }
+        Err(e @ config::ConfigError::InvalidFile(..)) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+        _ => config,
+    }
+}
+
+/// The colour theme from --theme or the config file, checked before any requests are made
+fn resolve_theme(cli: &Cli, config: &config::Config) -> Theme {
+    match Theme::resolve(config.theme.as_ref(), cli.theme.as_deref()) {
+        Ok(theme) => theme,
+        Err(e) => {
+            eprintln!("{} in {}", e, config::get_config_path().display());
+            std::process::exit(2);
+        }
+    }
+}
+
+/// Resolve an on/off flag pair, falling back to the config default and then off
+fn flag(on: bool, off: bool, default: Option<bool>) -> bool {
+    if on {
+        true
+    } else if off {
+        false
+    } else {
+        default.unwrap_or(false)
+    }
+}
+
+/// Resolve the search timeouts from the flags, then the config file, then the defaults
+fn timeouts(cli: &Cli, config: &config::Config) -> api::Timeouts {
+    let network = config.network.as_ref();
+    let defaults = api::Timeouts::default();
+    let limit = |flag: Option<u64>, setting: Option<u64>, default| match flag.or(setting) {
+        Some(0) => None,
+        Some(seconds) => Some(Duration::from_secs(seconds)),
+        None => default,
+    };
+
+    api::Timeouts {
+        connect: limit(
+            cli.connect_timeout,
+            network.and_then(|n| n.connect_timeout),
+            defaults.connect,
+        ),
+        overall: limit(
+            cli.timeout,
+            network.and_then(|n| n.timeout),
+            defaults.overall,
+        ),
+        idle: limit(
+            cli.idle_timeout,
+            network.and_then(|n| n.idle_timeout),
+            defaults.idle,
+        ),
+    }
+}
+
+/// The HTTP client for every request in this run, or exit after explaining a bad network setting
+fn http_client(cli: &Cli, config: &config::Config) -> reqwest::Client {
+    match client::build(config.network.as_ref(), timeouts(cli, config).connect) {
+        Ok(client) => client,
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    }
+}
+
+/// Exit unless the selected profile exists, before prompting or contacting the API to change it
+fn require_profile(config: &config::Config) {
+    if let Err(e) = config.ensure_profile() {
+        eprintln!("{}", e);
+        std::process::exit(1);
+    }
+}
+
+/// The token to send to the API, or `None` after explaining why there isn't one
+fn api_token(config: &config::Config) -> Option<String> {
+    if let Err(e) = config.ensure_profile() {
+        eprintln!("{}", e);
+        return None;
+    }
+    match config.get_token() {
+        Ok(token) => Some(token),
+        Err(config::ConfigError::VaultError(e)) => {
+            eprintln!("Could not unlock the API token: {}", e);
+            None
+        }
+        Err(_) => {
+            eprintln!(
+                "API token not set. Please log in with 'namekit login', or set a token with 'namekit config set-token <TOKEN>' or the NAMEKIT_TOKEN environment variable"
+            );
+            None
+        }
+    }
+}
+
+/// The prompts `search ai` runs. Unquoted words make up a single prompt, while quoted
+/// terms holding spaces, or any terms with `--each`, are prompts of their own.
+fn ai_queries(terms: &[String], each: bool) -> Vec<String> {
+    if each || terms.iter().any(|term| term.contains(char::is_whitespace)) {
+        terms.to_vec()
+    } else {
+        vec![terms.join(" ")]
+    }
+}
+
+/// Run a search for each query against the API at the same time and display the filtered
+/// results together, in the order they arrive
+async fn search(
+    cli: &Cli,
+    queries: &[String],
+    mode: &str,
+    template: Option<Template>,
+    file_output: Option<(BufWriter<File>, OutputMode)>,
+    use_color: bool,
+) -> Result<(), Box<dyn std::error::Error>> {
+    // Load config to get the API token
+    let config = load_config(cli)?;
+    let theme = resolve_theme(cli, &config);
+    let Some(token) = api_token(&config) else {
+        return Ok(());
+    };
+
+    // Flags take precedence over the defaults saved in the config file
+    let tlds = cli
+        .tlds
+        .clone()
+        .or_else(|| config.defaults.as_ref().and_then(|d| d.tlds.clone()))
+        .unwrap_or_else(|| api::DEFAULT_TLDS.to_string());
+
+    let mut recorder = match &cli.record {
+        Some(path) => match recording::Recorder::create(path) {
+            Ok(recorder) => Some(recorder),
+            Err(e) => {
+                eprintln!("Could not create {}: {}", path.display(), e);
+                std::process::exit(2);
+            }
+        },
+        None => None,
+    };
+
+    // One client for every query, so they can share connections to the API server
+    let client = http_client(cli, &config);
+    let api_server = config.get_api_server();
+    let timeouts = timeouts(cli, &config);
+
+    // --record is only allowed with a single query, so the recorder goes to the first search
+    let mut streams = Vec::new();
+    for query in queries {
+        let request = api::SearchRequest {
+            query: query.clone(),
+            mode: mode.to_string(),
+            tlds: tlds.clone(),
+        };
+        match api::stream_domains(
+            &client,
+            request,
+            &token,
+            &api_server,
+            timeouts,
+            recorder.take(),
+        )
+        .await
+        {
+            Ok(domain_stream) => streams.push(domain_stream),
+            Err(e) => {
+                eprintln!("Error fetching domain results for '{}': {}", query, e);
+            }
+        }
+    }
+    if streams.is_empty() {
+        return Ok(());
+    }
+
+    show_results(
+        cli,
+        &config,
+        futures_util::stream::select_all(streams),
+        queries,
+        template,
+        file_output,
+        use_color.then_some(&theme),
+    )
+    .await
+}
+
+/// Show the results of a search recorded with --record
+async fn replay(
+    cli: &Cli,
+    path: &Path,
+    template: Option<Template>,
+    file_output: Option<(BufWriter<File>, OutputMode)>,
+    use_color: bool,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let config = load_config(cli)?;
+    let theme = resolve_theme(cli, &config);
+    let recording = match recording::Recording::read(path) {
+        Ok(recording) => recording,
+        Err(e) => {
+            eprintln!("Could not replay {}: {}", path.display(), e);
+            std::process::exit(1);
+        }
+    };
+
+    let queries = [recording.header.query.clone()];
+    let domain_stream = api::replay_domains(recording);
+    show_results(
+        cli,
+        &config,
+        domain_stream,
+        &queries,
+        template,
+        file_output,
+        use_color.then_some(&theme),
+    )
+    .await
+}
+
+/// Filter, sort and render a stream of results to stdout and any --out file, coloured with
+/// `theme` unless it is `None`
+async fn show_results(
+    cli: &Cli,
+    config: &config::Config,
+    domain_stream: impl Stream<Item = DomainResult> + Unpin,
+    queries: &[String],
+    template: Option<Template>,
+    file_output: Option<(BufWriter<File>, OutputMode)>,
+    theme: Option<&Theme>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    // Flags take precedence over the defaults saved in the config file
+    let defaults = config.defaults.as_ref();
+    let output = cli
+        .output
+        .clone()
+        .or_else(|| defaults.and_then(|d| d.output.clone()))
+        .unwrap_or_else(|| "grid".to_string());
+    let sort = cli
+        .sort
+        .as_deref()
+        .or_else(|| defaults.and_then(|d| d.sort.as_deref()))
+        .and_then(SortOrder::parse);
+    let filter = Filter {
+        show_taken: flag(
+            cli.show_taken,
+            cli.no_show_taken,
+            defaults.and_then(|d| d.show_taken),
+        ),
+        hide_premium: flag(
+            cli.hide_premium,
+            cli.no_hide_premium,
+            defaults.and_then(|d| d.hide_premium),
+        ),
+    };
+
+    // Determine output mode, with a template taking over from the list/grid layout
+    let output_mode = match template {
+        Some(template) => OutputMode::Template(template),
+        None => match output.to_lowercase().as_str() {
+            "grid" => OutputMode::Grid,
+            "json" => OutputMode::Json,
+            "markdown" | "md" => OutputMode::Markdown,
+            "html" => OutputMode::Html,
+            _ => OutputMode::List,
+        },
+    };
+
+    // Keep machine readable stdout valid, and redirected results free of anything but
+    // domains, by sending the summary to stderr
+    let footer = if cli.quiet {
+        Footer::None
+    } else if output_mode.is_machine_readable() || !io::stdout().is_terminal() {
+        Footer::Stderr
+    } else {
+        Footer::Stdout
+    };
+
+    // Sections only make sense for the layouts meant for reading, and with something to group
+    let sections = (cli.sections
+        && queries.len() > 1
+        && matches!(output_mode, OutputMode::List | OutputMode::Grid))
+    .then_some(queries);
+
+    let mut renderers = vec![output::renderer(
+        output_mode,
+        io::stdout(),
+        theme,
+        queries,
+        output::terminal_width(),
+    )];
+
+    // Tee the results into a file when requested, never coloured
+    if let Some((file, file_mode)) = file_output {
+        renderers.push(output::renderer(file_mode, file, None, queries, 80));
+    }
+
+    // Stop reading results on Ctrl-C, so the renderers still finish their output (e.g. close
+    // the JSON array) and the search task sees the stream is no longer wanted
+    let mut domain_stream = domain_stream.take_until(Box::pin(async {
+        if tokio::signal::ctrl_c().await.is_err() {
+            std::future::pending::<()>().await;
+        }
+    }));
+
+    // Display the filtered results
+    display_results(
+        &mut domain_stream,
+        renderers,
+        &filter,
+        sort,
+        sections,
+        footer,
+    )
+    .await?;
+
+    if domain_stream.take_result().is_some() {
+        if theme.is_some() {
+            let _ = crossterm::execute!(io::stdout(), crossterm::style::ResetColor);
+        }
+        eprintln!("Search interrupted");
+        std::process::exit(EXIT_INTERRUPTED);
+    }
+
+    Ok(())
+}
+
+/// The API token given on the command line or, without one, typed at a prompt that doesn't
+/// echo it, so it stays out of the shell history. `hint` says how to pass it without a terminal.
+fn read_token(given: Option<&str>, hint: &str) -> Zeroizing<String> {
+    let token = match given {
+        Some(token) => Zeroizing::new(token.to_string()),
+        None => match rpassword::prompt_password("API token: ") {
+            Ok(token) => Zeroizing::new(token),
+            Err(e) => {
+                eprintln!("Could not read the API token ({}). {}", e, hint);
+                std::process::exit(2);
+            }
+        },
+    };
+    let token = Zeroizing::new(token.trim().to_string());
+    if token.is_empty() {
+        eprintln!("No API token entered");
+        std::process::exit(2);
+    }
+    token
+}
+
+/// Verify a token against the account endpoint and save it only if the API accepts it
+async fn login(cli: &Cli, encrypt: bool) -> Result<(), Box<dyn std::error::Error>> {
+    let mut config = load_config(cli)?;
+    require_profile(&config);
+
+    // --token skips the prompt, which is handy for scripts
+    let token = read_token(cli.token.as_deref(), "Pass it with --token");
+    let token = token.as_str();
+
+    let server = config.get_api_server();
+    let account = match api::fetch_account(&http_client(cli, &config), &server, token).await {
+        Ok(account) => account,
+        Err(e) => {
+            eprintln!("Login failed: {}", e);
+            eprintln!("The saved API token was not changed");
+            std::process::exit(1);
+        }
+    };
+
+    let saved = if encrypt {
+        config.set_encrypted_token(token.to_string())
+    } else {
+        config.set_token(token.to_string())
+    };
+    if let Err(e) = saved {
+        eprintln!("Could not save the API token: {}", e);
+        std::process::exit(1);
+    }
+
+    let who = account.email.as_deref().unwrap_or("your account");
+    match &account.plan {
+        Some(plan) => println!("Logged in to {} as {} ({} plan)", server, who, plan),
+        None => println!("Logged in to {} as {}", server, who),
+    }
+    println!(
+        "API token saved for profile '{}'",
+        config.active_profile_name()
+    );
+    Ok(())
+}
+
+/// Print the account's plan and query allowance
+async fn usage(cli: &Cli) -> Result<(), Box<dyn std::error::Error>> {
+    let config = load_config(cli)?;
+    let Some(token) = api_token(&config) else {
+        std::process::exit(1);
+    };
+
+    let account = match api::fetch_account(
+        &http_client(cli, &config),
+        &config.get_api_server(),
+        &token,
+    )
+    .await
+    {
+        Ok(account) => account,
+        Err(e) => {
+            eprintln!("Could not fetch usage: {}", e);
+            std::process::exit(1);
+        }
+    };
+
+    if let Some(email) = &account.email {
+        println!("Account: {}", email);
+    }
+    println!("Plan: {}", account.plan.as_deref().unwrap_or("unknown"));
+
+    let Some(usage) = &account.usage else {
+        println!("Usage: not reported by the API");
+        return Ok(());
+    };
+    match (usage.remaining, usage.limit) {
+        (Some(remaining), Some(limit)) => println!("Queries remaining: {} of {}", remaining, limit),
+        (Some(remaining), None) => println!("Queries remaining: {}", remaining),
+        (None, Some(limit)) => println!("Query limit: {}", limit),
+        (None, None) => {}
+    }
+    if let Some(reset) = usage.reset_time() {
+        println!("Resets: {}", api::describe_reset(reset));
+    }
+
+    Ok(())
+}
+
+/// Send log events to stderr at the level chosen with -v/-vv/--debug. Without any of
+/// them nothing is logged.
+fn init_logging(verbose: u8, debug: bool) {
+    use tracing_subscriber::filter::{LevelFilter, Targets};
+    use tracing_subscriber::prelude::*;
+
+    let (own, dependencies) = match (debug, verbose) {
+        (true, _) => (LevelFilter::TRACE, LevelFilter::DEBUG),
+        (false, 0) => return,
+        (false, 1) => (LevelFilter::INFO, LevelFilter::OFF),
+        (false, _) => (LevelFilter::DEBUG, LevelFilter::OFF),
+    };
+
+    let ansi = io::stderr().is_terminal() && std::env::var_os("NO_COLOR").is_none();
+    tracing_subscriber::registry()
+        .with(
+            tracing_subscriber::fmt::layer()
+                .with_writer(io::stderr)
+                .with_ansi(ansi)
+                .with_timer(tracing_subscriber::fmt::time::uptime()),
+        )
+        .with(
+            Targets::new()
+                .with_default(dependencies)
+                .with_target(env!("CARGO_CRATE_NAME"), own),
+        )
+        .init();
+}
+
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
     let cli = Cli::parse();
+    init_logging(cli.verbose, cli.debug);
 
-    // Determine output mode
-    let output_mode = match cli.output.to_lowercase().as_str() {
-        "grid" => OutputMode::Grid,
-        "json" => OutputMode::Json,
-        _ => OutputMode::List,
-    };
-
-    match &cli.command {
-        Commands::Search { mode } => {
-            match mode {
-                SearchMode::AI { terms } => {
-                    // Load config to get the API token
-                    let config = config::Config::load()?;
-                    let token = match config.get_token() {
-                        Ok(token) => token,
-                        Err(_) => {
-                            eprintln!(
-                                "API token not set. Please set a token with 'namekit config set-token <TOKEN>'"
-                            );
-                            return Ok(());
-                        }
-                    };
+    // Check the --format template before making any requests
+    let template = match cli.format.as_deref().map(Template::parse) {
+        Some(Ok(template)) => Some(template),
+        Some(Err(e)) => {
+            eprintln!("Invalid --format template: {}", e);
+            std::process::exit(2);
+        }
+        None => None,
+    };
 
-                    match api::stream_domains(&terms.join(" "), "ai", &token).await {
-                        Ok(domain_stream) => {
-                            // Filter the stream based on flags
-                            let filtered_stream = domain_stream
-                                .filter(move |domain| {
-                                    let show = (domain.available || cli.show_taken)
-                                        && (!domain.premium || !cli.hide_premium);
-                                    async move { show }
-                                })
-                                .boxed(); // Box the stream to make it Unpin
-
-                            // Display the filtered results
-                            display_results(filtered_stream, output_mode).await?;
-                        }
-                        Err(e) => {
-                            eprintln!("Error fetching domain results: {}", e);
-                        }
-                    }
+    // Determine the format of the --out file and create it before making any requests, so a
+    // bad path doesn't use up a query. Commands that don't show results leave it alone.
+    let shows_results =
+        cli.replay.is_some() || matches!(cli.command, Some(Commands::Search { .. }));
+    let file_output = match &cli.out {
+        Some(path) if shows_results => match OutputMode::from_path(path) {
+            Some(mode) => match File::create(path) {
+                Ok(file) => Some((BufWriter::new(file), mode)),
+                Err(e) => {
+                    eprintln!("Could not create {}: {}", path.display(), e);
+                    std::process::exit(2);
                 }
-                SearchMode::Tld { query } => {
-                    // Load config to get the API token
-                    let config = config::Config::load()?;
-                    let token = match config.get_token() {
-                        Ok(token) => token,
-                        Err(_) => {
-                            eprintln!(
-                                "API token not set. Please set a token with 'namekit config set-token <TOKEN>'"
-                            );
-                            return Ok(());
-                        }
-                    };
+            },
+            None => {
+                eprintln!(
+                    "Cannot infer an output format from '{}'. Use a .json, .md, .html or .txt extension",
+                    path.display()
+                );
+                std::process::exit(2);
+            }
+        },
+        _ => None,
+    };
 
-                    match api::stream_domains(query, "tld", &token).await {
-                        Ok(domain_stream) => {
-                            // Filter the stream based on flags
-                            let filtered_stream = domain_stream
-                                .filter(move |domain| {
-                                    let show = (domain.available || cli.show_taken)
-                                        && (!domain.premium || !cli.hide_premium);
-                                    async move { show }
-                                })
-                                .boxed(); // Box the stream to make it Unpin
-
-                            // Display the filtered results
-                            display_results(filtered_stream, output_mode).await?;
-                        }
-                        Err(e) => {
-                            eprintln!("Error fetching domain results: {}", e);
-                        }
-                    }
+    // Determine whether to colour output
+    let use_color = match cli.color.as_str() {
+        "always" => ColorChoice::Always,
+        "never" => ColorChoice::Never,
+        _ => ColorChoice::Auto,
+    }
+    .use_color();
+
+    let command = match (&cli.command, &cli.replay) {
+        (Some(command), None) => command,
+        (None, Some(path)) => return replay(&cli, path, template, file_output, use_color).await,
+        (Some(_), Some(_)) => {
+            eprintln!(
+                "--replay takes the query from the recording and can't be used with a command"
+            );
+            std::process::exit(2);
+        }
+        (None, None) => {
+            Cli::command().print_help()?;
+            std::process::exit(2);
+        }
+    };
+    if cli.record.is_some() && !matches!(command, Commands::Search { .. }) {
+        eprintln!("--record can only be used with 'search'");
+        std::process::exit(2);
+    }
+    let several_queries = match command {
+        Commands::Search {
+            mode: SearchMode::AI { terms, each },
+        } => ai_queries(terms, *each).len() > 1,
+        Commands::Search {
+            mode: SearchMode::Tld { names },
+        } => names.len() > 1,
+        _ => false,
+    };
+    if cli.record.is_some() && several_queries {
+        eprintln!("--record saves a single search and can't be used with several queries");
+        std::process::exit(2);
+    }
+
+    match command {
+        Commands::Search { mode } => match mode {
+            SearchMode::AI { terms, each } => {
+                let queries = ai_queries(terms, *each);
+                search(&cli, &queries, "ai", template, file_output, use_color).await?;
+            }
+            SearchMode::Tld { names } => {
+                search(&cli, names, "tld", template, file_output, use_color).await?;
+            }
+        },
+        Commands::Login { encrypt } => login(&cli, *encrypt).await?,
+        Commands::Usage => usage(&cli).await?,
+        Commands::Logout => {
+            let mut config = load_config(&cli)?;
+            let profile = config.active_profile_name().to_string();
+            if config.has_saved_token() {
+                if let Err(e) = config.clear_token() {
+                    eprintln!("Could not remove the API token: {}", e);
+                    std::process::exit(1);
                 }
+                config.save()?;
+                println!("Logged out of profile '{}'", profile);
+            } else {
+                println!("No API token is saved for profile '{}'", profile);
+            }
+            if let Some((_, source)) = config.token_with_source() {
+                println!("A token is still being supplied by the {}", source);
             }
         }
         Commands::Config { action } => {
             match action {
-                ConfigCommands::SetToken { token } => {
-                    let mut config = config::Config::load()?;
-                    config.set_token(token.clone())?;
-                    println!("API token set successfully");
+                ConfigCommands::SetToken { token, encrypt } => {
+                    let mut config = load_config(&cli)?;
+                    require_profile(&config);
+                    let token = read_token(token.as_deref(), "Pass it as an argument");
+                    let saved = if *encrypt {
+                        config.set_encrypted_token(token.to_string())
+                    } else {
+                        config.set_token(token.to_string())
+                    };
+                    if let Err(e) = saved {
+                        eprintln!("Could not save the API token: {}", e);
+                        std::process::exit(1);
+                    }
+                    if *encrypt {
+                        println!(
+                            "API token encrypted and stored for profile '{}' in {}",
+                            config.active_profile_name(),
+                            config::get_vault_path().display()
+                        );
+                    } else {
+                        println!(
+                            "API token set successfully for profile '{}'",
+                            config.active_profile_name()
+                        );
+                    }
 
                     // Show the config file path for reference
                     let path = config::get_config_path();
                     println!("Configuration saved to: {}", path.display());
                 }
-                ConfigCommands::SetApiServer { server } => {
-                    let mut config = config::Config::load()?;
-                    config.set_api_server(server.clone())?;
-                    println!("API server set to: {}", server);
+                ConfigCommands::SetApiServer { server, check } => {
+                    let mut config = load_config(&cli)?;
+                    require_profile(&config);
+                    let server = match config::parse_api_server(server) {
+                        Ok(server) => server,
+                        Err(expected) => {
+                            eprintln!("Invalid API server '{}': expected {}", server, expected);
+                            std::process::exit(2);
+                        }
+                    };
+                    if *check {
+                        if let Err(e) =
+                            api::check_health(&http_client(&cli, &config), &server).await
+                        {
+                            eprintln!("Could not reach {}: {}", server, e);
+                            eprintln!("The API server was not changed");
+                            std::process::exit(1);
+                        }
+                        println!("{} is reachable", server);
+                    }
+                    let server = config.set_api_server(&server)?;
+                    println!(
+                        "API server for profile '{}' set to: {}",
+                        config.active_profile_name(),
+                        server
+                    );
 
                     // Show the config file path for reference
                     let path = config::get_config_path();
                     println!("Configuration saved to: {}", path.display());
                 }
+                ConfigCommands::Use { profile } => {
+                    let mut config = config::Config::load(&config::Overrides::default())?;
+                    if config.use_profile(profile)? {
+                        println!("Created profile '{}'", profile);
+                    }
+                    println!("Now using profile '{}'", profile);
+                }
+                ConfigCommands::Get { key } => {
+                    let config = load_config(&cli)?;
+                    match settings::get(&config, key) {
+                        Ok(Some(value)) => println!("{}", value),
+                        Ok(None) => {
+                            eprintln!("'{}' is not set", key);
+                            std::process::exit(1);
+                        }
+                        Err(e) => {
+                            eprintln!("{}", e);
+                            std::process::exit(2);
+                        }
+                    }
+                }
+                ConfigCommands::Set { key, value } => {
+                    let mut config = load_config(&cli)?;
+                    if let Err(e) = settings::set(&mut config, key, value) {
+                        eprintln!("{}", e);
+                        std::process::exit(2);
+                    }
+                    config.save()?;
+                    let shown = settings::lookup(key)?.display_value(value);
+                    println!("{} set to: {}", key, shown);
+                }
+                ConfigCommands::Unset { key } => {
+                    let mut config = load_config(&cli)?;
+                    if let Err(e) = settings::unset(&mut config, key) {
+                        eprintln!("{}", e);
+                        std::process::exit(2);
+                    }
+                    config.save()?;
+                    println!("{} unset", key);
+                }
+                ConfigCommands::Edit => {
+                    if let Err(e) = config::edit_config_file() {
+                        eprintln!("{}", e);
+                        std::process::exit(1);
+                    }
+                    println!(
+                        "Configuration saved to: {}",
+                        config::get_config_path().display()
+                    );
+                }
+                ConfigCommands::Migrate { to } => {
+                    let format = to.as_deref().and_then(config::ConfigFormat::parse);
+                    match config::migrate_config_file(format) {
+                        Ok(migration) => {
+                            if migration.from_version < config::CONFIG_VERSION {
+                                println!(
+                                    "Upgraded config from version {} to version {}",
+                                    migration.from_version,
+                                    config::CONFIG_VERSION
+                                );
+                            }
+                            println!("Configuration saved to: {}", migration.path.display());
+                            if let Some(backup) = migration.backup {
+                                println!("The previous file was moved to: {}", backup.display());
+                                if std::env::var_os(config::CONFIG_ENV).is_some() {
+                                    println!(
+                                        "Point {} at the new file to keep using it",
+                                        config::CONFIG_ENV
+                                    );
+                                }
+                            }
+                        }
+                        Err(e) => {
+                            eprintln!(
+                                "Could not migrate {}: {}",
+                                config::get_config_path().display(),
+                                e
+                            );
+                            std::process::exit(1);
+                        }
+                    }
+                }
                 ConfigCommands::Show => {
-                    let config = config::Config::load()?;
+                    let config = load_config(&cli)?;
                     println!("Current configuration:");
+                    println!("Profile: {}", config.active_profile_name());
 
-                    match config.get_token() {
-                        Ok(token) => {
-                            // Only show a masked version of the token for security
-                            let masked_token = if token.len() > 8 {
-                                format!("{}...{}", &token[0..4], &token[token.len() - 4..])
-                            } else {
-                                "****".to_string()
-                            };
-                            println!("API Token: {}", masked_token);
+                    // Only show a masked version of the token for security
+                    match config.token_with_source() {
+                        Some((config::StoredToken::Plain(token), source)) => {
+                            println!(
+                                "API Token: {} (from {})",
+                                config::mask_token(&token),
+                                source
+                            )
                         }
-                        Err(_) => {
-                            println!("API Token: Not set");
+                        Some((config::StoredToken::Encrypted, source)) => {
+                            println!("API Token: Encrypted (from {})", source)
                         }
+                        None => println!("API Token: Not set"),
                     }
 
-                    // Show the API server
-                    println!("API Server: {}", config.get_api_server());
+                    let (server, source) = config.api_server_with_source();
+                    println!("API Server: {} (from {})", server, source);
+
+                    println!();
+                    println!("Profiles:");
+                    for (name, profile) in &config.profiles {
+                        let marker = if name == config.active_profile_name() {
+                            "*"
+                        } else {
+                            " "
+                        };
+                        println!("{} {}", marker, name);
+
+                        // Only show a masked version of the token for security
+                        match &profile.api_token {
+                            Some(token) => println!("    API Token: {}", config::mask_token(token)),
+                            None if profile.token_encrypted => println!("    API Token: Encrypted"),
+                            None => println!("    API Token: Not set"),
+                        }
+
+                        println!("    API Server: {}", profile.api_server());
+                    }
+
+                    if config.ensure_profile().is_err() {
+                        println!(
+                            "Profile '{}' is selected but does not exist",
+                            config.active_profile_name()
+                        );
+                    }
 
                     let path = config::get_config_path();
                     println!("Configuration file: {}", path.display());
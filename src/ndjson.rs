@@ -0,0 +1,206 @@
+use std::fmt;
+use std::str::Utf8Error;
+
+#[derive(Debug, PartialEq)]
+pub enum LineError {
+    /// The line was longer than the decoder's limit and was dropped
+    TooLong(usize),
+    InvalidUtf8(Utf8Error),
+}
+
+impl fmt::Display for LineError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LineError::TooLong(limit) => write!(f, "line is longer than {} bytes", limit),
+            LineError::InvalidUtf8(e) => write!(f, "line is not valid UTF-8: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for LineError {}
+
+/// Splits a newline-delimited stream into lines as chunks of bytes arrive
+///
+/// Bytes are only decoded once a whole line has been received, so a multi-byte
+/// character split across two chunks comes out intact. Blank lines are skipped and a
+/// trailing `\r` is removed. A line longer than `max_line_length` is dropped as soon
+/// as it passes the limit, rather than buffered, and reported as `LineError::TooLong`.
+pub struct LineDecoder {
+    buffer: Vec<u8>,
+    max_line_length: usize,
+    /// The current line passed the limit; its remaining bytes are discarded
+    overflowed: bool,
+}
+
+impl LineDecoder {
+    pub fn new(max_line_length: usize) -> Self {
+        Self {
+            buffer: Vec::new(),
+            max_line_length,
+            overflowed: false,
+        }
+    }
+
+    /// Add a chunk and return every line it completes
+    pub fn push(&mut self, chunk: &[u8]) -> Vec<Result<String, LineError>> {
+        let mut lines = Vec::new();
+        let mut rest = chunk;
+
+        while let Some(newline) = rest.iter().position(|&b| b == b'\n') {
+            self.extend(&rest[..newline]);
+            lines.extend(self.take_line());
+            rest = &rest[newline + 1..];
+        }
+        self.extend(rest);
+
+        lines
+    }
+
+    /// Return the final line if the stream didn't end with a newline
+    pub fn finish(&mut self) -> Option<Result<String, LineError>> {
+        self.take_line()
+    }
+
+    fn extend(&mut self, bytes: &[u8]) {
+        if self.overflowed {
+            return;
+        }
+        if self.buffer.len() + bytes.len() > self.max_line_length {
+            self.overflowed = true;
+            self.buffer = Vec::new();
+            return;
+        }
+        self.buffer.extend_from_slice(bytes);
+    }
+
+    fn take_line(&mut self) -> Option<Result<String, LineError>> {
+        if std::mem::take(&mut self.overflowed) {
+            return Some(Err(LineError::TooLong(self.max_line_length)));
+        }
+
+        let mut bytes = std::mem::take(&mut self.buffer);
+        if bytes.last() == Some(&b'\r') {
+            bytes.pop();
+        }
+
+        match String::from_utf8(bytes) {
+            Ok(line) if line.trim().is_empty() => None,
+            Ok(line) => Some(Ok(line)),
+            Err(e) => Some(Err(LineError::InvalidUtf8(e.utf8_error()))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    /// Feed `input` to a decoder in pieces cut at `splits` and collect every line
+    fn decode(
+        input: &[u8],
+        splits: &[usize],
+        max_line_length: usize,
+    ) -> Vec<Result<String, LineError>> {
+        let mut decoder = LineDecoder::new(max_line_length);
+        let mut cuts: Vec<usize> = splits.iter().map(|s| s % (input.len() + 1)).collect();
+        cuts.sort_unstable();
+
+        let mut lines = Vec::new();
+        let mut start = 0;
+        for cut in cuts.into_iter().chain([input.len()]) {
+            lines.extend(decoder.push(&input[start..cut]));
+            start = cut;
+        }
+        lines.extend(decoder.finish());
+        lines
+    }
+
+    #[test]
+    fn splits_lines_and_keeps_the_unterminated_last_one() {
+        let lines = decode(b"{\"a\":1}\n{\"b\":2}\n{\"c\":3}", &[], 1024);
+        assert_eq!(
+            lines,
+            [
+                Ok("{\"a\":1}".into()),
+                Ok("{\"b\":2}".into()),
+                Ok("{\"c\":3}".into())
+            ]
+        );
+    }
+
+    #[test]
+    fn skips_blank_lines_and_strips_carriage_returns() {
+        let lines = decode(b"one\r\n\r\n  \ntwo\n\n", &[], 1024);
+        assert_eq!(lines, [Ok("one".into()), Ok("two".into())]);
+    }
+
+    #[test]
+    fn keeps_characters_split_across_chunks() {
+        let input = "{\"domain\": \"caf\u{e9}.com\"}\n".as_bytes();
+        let split = input.iter().position(|&b| b == 0xc3).unwrap() + 1;
+
+        let lines = decode(input, &[split], 1024);
+        assert_eq!(lines, [Ok("{\"domain\": \"caf\u{e9}.com\"}".into())]);
+    }
+
+    #[test]
+    fn reports_invalid_utf8_and_carries_on() {
+        let lines = decode(b"bad \xff line\ngood\n", &[], 1024);
+        assert!(matches!(lines[0], Err(LineError::InvalidUtf8(_))));
+        assert_eq!(lines[1], Ok("good".into()));
+    }
+
+    #[test]
+    fn drops_overlong_lines_without_buffering_them() {
+        let mut decoder = LineDecoder::new(8);
+        assert!(decoder.push(b"0123456789").is_empty());
+        assert!(decoder.buffer.is_empty(), "the overlong line isn't kept");
+
+        let lines = decoder.push(b"abcdef\nshort\n");
+        assert_eq!(lines, [Err(LineError::TooLong(8)), Ok("short".into())]);
+        assert_eq!(decoder.finish(), None);
+    }
+
+    #[test]
+    fn accepts_a_line_exactly_at_the_limit() {
+        assert_eq!(decode(b"12345678\n", &[], 8), [Ok("12345678".into())]);
+        assert_eq!(decode(b"123456789", &[], 8), [Err(LineError::TooLong(8))]);
+    }
+
+    proptest! {
+        /// Any text lines, however the bytes are chunked, come back exactly
+        #[test]
+        fn round_trips_lines_across_any_chunking(
+            lines in prop::collection::vec("[^\n\r]*[^\\s][^\n\r]*", 0..20),
+            trailing_newline in any::<bool>(),
+            splits in prop::collection::vec(any::<usize>(), 0..30),
+        ) {
+            let mut input = lines.join("\n");
+            if trailing_newline {
+                input.push('\n');
+            }
+
+            let decoded = decode(input.as_bytes(), &splits, usize::MAX);
+            let expected: Vec<Result<String, LineError>> = lines.into_iter().map(Ok).collect();
+            prop_assert_eq!(decoded, expected);
+        }
+
+        /// Arbitrary bytes never panic, and chunking never changes the result
+        #[test]
+        fn chunking_does_not_change_the_output(
+            input in prop::collection::vec(any::<u8>(), 0..512),
+            splits in prop::collection::vec(any::<usize>(), 0..30),
+            max_line_length in 1usize..64,
+        ) {
+            let whole = decode(&input, &[], max_line_length);
+            let chunked = decode(&input, &splits, max_line_length);
+            prop_assert_eq!(&whole, &chunked);
+
+            for line in whole.iter().flatten() {
+                prop_assert!(line.len() <= max_line_length);
+                prop_assert!(!line.contains('\n'));
+            }
+        }
+    }
+}
@@ -1,133 +1,1029 @@
-use crate::domain::DomainResult;
-use crossterm::{
-    execute,
-    style::{Color as CrosstermColor, ResetColor, SetForegroundColor},
-};
+use crate::domain::{DomainResult, DomainStatus};
+use crate::template::Template;
+use crate::theme::Theme;
+use crossterm::queue;
+use crossterm::style::{PrintStyledContent, Stylize};
 use futures_core::stream::Stream;
 use futures_util::StreamExt;
-use std::io;
+use std::env;
+use std::fmt;
+use std::io::{self, IsTerminal, Write};
+use std::path::Path;
+use std::time::{Duration, Instant};
 
 pub enum OutputMode {
     List,
     Grid,
     Json,
+    Markdown,
+    Html,
+    /// One line per domain rendered from a user supplied `--format` template
+    Template(Template),
 }
 
-pub async fn display_list<S>(stream: S) -> io::Result<()>
-where
-    S: Stream<Item = DomainResult> + Unpin,
-{
-    let mut stream = Box::pin(stream);
+impl OutputMode {
+    /// Infer the format to write to a file from its extension
+    pub fn from_path(path: &Path) -> Option<Self> {
+        let extension = path.extension()?.to_str()?.to_lowercase();
+        match extension.as_str() {
+            "json" => Some(OutputMode::Json),
+            "md" | "markdown" => Some(OutputMode::Markdown),
+            "html" | "htm" => Some(OutputMode::Html),
+            "txt" => Some(OutputMode::List),
+            _ => None,
+        }
+    }
 
-    while let Some(result) = stream.next().await {
-        let color = if result.premium {
-            CrosstermColor::Yellow
-        } else if result.available {
-            CrosstermColor::Green
-        } else {
-            CrosstermColor::Red
+    /// Whether the output is meant for other programs, so anything extra goes to stderr
+    pub fn is_machine_readable(&self) -> bool {
+        !matches!(self, OutputMode::List | OutputMode::Grid)
+    }
+}
+
+/// Names accepted by `--output`
+pub const OUTPUT_FORMATS: [&str; 5] = ["grid", "list", "json", "markdown", "html"];
+
+/// Names accepted by `--sort`
+pub const SORT_ORDERS: [&str; 4] = ["none", "name", "tld", "status"];
+
+/// Order to show results in once the search has finished
+#[derive(Clone, Copy)]
+pub enum SortOrder {
+    Name,
+    Tld,
+    Status,
+}
+
+impl SortOrder {
+    /// Parse a `--sort` value, where `none` (stream order) gives `None`
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.to_lowercase().as_str() {
+            "name" => Some(SortOrder::Name),
+            "tld" => Some(SortOrder::Tld),
+            "status" => Some(SortOrder::Status),
+            _ => None,
+        }
+    }
+
+    fn sort(self, results: &mut [DomainResult]) {
+        let tld = |result: &DomainResult| {
+            result
+                .name
+                .split_once('.')
+                .map_or(String::new(), |(_, tld)| tld.to_string())
         };
-        execute!(io::stdout(), SetForegroundColor(color))?;
-        println!("{}", result.name);
-        execute!(io::stdout(), ResetColor)?;
+        let rank = |result: &DomainResult| match result.status() {
+            DomainStatus::Available => 0,
+            DomainStatus::Premium => 1,
+            DomainStatus::Taken => 2,
+            DomainStatus::Unknown => 3,
+        };
+
+        match self {
+            SortOrder::Name => results.sort_by(|a, b| a.name.cmp(&b.name)),
+            SortOrder::Tld => results.sort_by(|a, b| tld(a).cmp(&tld(b)).then(a.name.cmp(&b.name))),
+            SortOrder::Status => {
+                results.sort_by(|a, b| rank(a).cmp(&rank(b)).then(a.name.cmp(&b.name)))
+            }
+        }
     }
-    Ok(())
 }
 
-pub async fn display_grid<S>(stream: S) -> io::Result<()>
-where
-    S: Stream<Item = DomainResult> + Unpin,
-{
-    let (width, _) = crossterm::terminal::size()?;
+/// Which domains to show, as selected with `--show-taken` and `--hide-premium`
+pub struct Filter {
+    pub show_taken: bool,
+    pub hide_premium: bool,
+}
 
-    // Use static max domain length of 20 as specified
-    let max_domain_length = 20;
+impl Filter {
+    pub fn shows(&self, result: &DomainResult) -> bool {
+        (result.is_available() || self.show_taken) && (!result.premium || !self.hide_premium)
+    }
+}
 
-    let column_width = max_domain_length + 4; // Add some padding
-    let num_columns = std::cmp::max(1, width as usize / column_width);
+/// Where to print the summary footer once the stream ends
+pub enum Footer {
+    None,
+    Stdout,
+    Stderr,
+}
 
-    let mut current_col = 0;
-    let mut stream = Box::pin(stream);
+/// Counts collected while a search streams in
+#[derive(Default)]
+pub struct Summary {
+    checked: usize,
+    available: usize,
+    premium: usize,
+    hidden: usize,
+    elapsed: Duration,
+}
 
-    while let Some(result) = stream.next().await {
-        let color = if result.premium {
-            CrosstermColor::Yellow
-        } else if result.available {
-            CrosstermColor::Green
-        } else {
-            CrosstermColor::Red
+impl Summary {
+    fn record(&mut self, result: &DomainResult, shown: bool) {
+        self.checked += 1;
+        match result.status() {
+            DomainStatus::Available => self.available += 1,
+            DomainStatus::Premium => self.premium += 1,
+            DomainStatus::Taken | DomainStatus::Unknown => {}
+        }
+        if !shown {
+            self.hidden += 1;
+        }
+    }
+}
+
+impl fmt::Display for Summary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} checked \u{b7} {} available \u{b7} {} premium",
+            self.checked, self.available, self.premium
+        )?;
+        if self.hidden > 0 {
+            write!(f, " \u{b7} {} hidden", self.hidden)?;
+        }
+        write!(f, " \u{b7} {:.1}s", self.elapsed.as_secs_f64())
+    }
+}
+
+/// When to emit colour escapes, as selected with `--color`
+#[derive(Clone, Copy)]
+pub enum ColorChoice {
+    Auto,
+    Always,
+    Never,
+}
+
+impl ColorChoice {
+    /// Decide whether to colour output, honouring `NO_COLOR`, `CLICOLOR_FORCE`
+    /// and whether stdout is a terminal when the choice is `Auto`
+    pub fn use_color(self) -> bool {
+        match self {
+            ColorChoice::Always => true,
+            ColorChoice::Never => false,
+            ColorChoice::Auto => {
+                if env::var_os("NO_COLOR").is_some_and(|v| !v.is_empty()) {
+                    return false;
+                }
+                if env::var_os("CLICOLOR_FORCE").is_some_and(|v| !v.is_empty() && v != "0") {
+                    return true;
+                }
+                io::stdout().is_terminal()
+            }
+        }
+    }
+}
+
+/// Marker used in place of colour so the domain state survives plain-text output
+fn domain_marker(result: &DomainResult) -> Option<char> {
+    match result.status() {
+        DomainStatus::Available => None,
+        DomainStatus::Taken => Some('x'),
+        DomainStatus::Premium => Some('*'),
+        DomainStatus::Unknown => Some('?'),
+    }
+}
+
+fn plain_label(result: &DomainResult) -> String {
+    match domain_marker(result) {
+        Some(marker) => format!("{} {}", result.name, marker),
+        None => result.name.clone(),
+    }
+}
+
+/// Width used to lay out the grid, falling back to a conventional width when stdout is not a terminal
+pub fn terminal_width() -> u16 {
+    crossterm::terminal::size().map_or(80, |(width, _)| width)
+}
+
+/// Writes domain results in one output format as they arrive from the stream
+pub trait Renderer {
+    /// Write anything that precedes the first result
+    fn begin(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn render(&mut self, result: &DomainResult) -> io::Result<()>;
+
+    /// Start a group of results for one query. Formats without headings ignore it.
+    fn section(&mut self, _query: &str) -> io::Result<()> {
+        Ok(())
+    }
+
+    /// Write anything that follows the last result and flush the writer
+    fn finish(&mut self) -> io::Result<()>;
+}
+
+/// Create the renderer for `mode` writing to `out`
+///
+/// `theme` is `None` when colour is disabled, in which case plain-text markers are used instead.
+/// `queries` are shown in report headers, and reports get a query column when there is more
+/// than one. `width` is the number of columns available to the grid.
+pub fn renderer<W: Write + 'static>(
+    mode: OutputMode,
+    out: W,
+    theme: Option<&Theme>,
+    queries: &[String],
+    width: u16,
+) -> Box<dyn Renderer> {
+    let show_query = queries.len() > 1;
+    match mode {
+        OutputMode::List => Box::new(ListRenderer {
+            out,
+            theme: theme.cloned(),
+            sections: 0,
+        }),
+        OutputMode::Grid => Box::new(GridRenderer::new(out, theme.cloned(), width)),
+        OutputMode::Json => Box::new(JsonRenderer { out, first: true }),
+        OutputMode::Markdown => Box::new(MarkdownRenderer { out, show_query }),
+        OutputMode::Html => Box::new(HtmlRenderer {
+            out,
+            title: queries.join(", "),
+            show_query,
+        }),
+        OutputMode::Template(template) => Box::new(TemplateRenderer { out, template }),
+    }
+}
+
+/// Write the heading of a section, after a blank line if it isn't the first
+fn write_heading(out: &mut impl Write, query: &str, bold: bool, first: bool) -> io::Result<()> {
+    if !first {
+        writeln!(out)?;
+    }
+    let heading = format!("== {} ==", query);
+    if bold {
+        queue!(out, PrintStyledContent(heading.bold()))?;
+        writeln!(out)
+    } else {
+        writeln!(out, "{}", heading)
+    }
+}
+
+/// One domain per line
+pub struct ListRenderer<W> {
+    out: W,
+    theme: Option<Theme>,
+    /// Number of sections started so far
+    sections: usize,
+}
+
+impl<W: Write> Renderer for ListRenderer<W> {
+    fn section(&mut self, query: &str) -> io::Result<()> {
+        write_heading(
+            &mut self.out,
+            query,
+            self.theme.is_some(),
+            self.sections == 0,
+        )?;
+        self.sections += 1;
+        Ok(())
+    }
+
+    fn render(&mut self, result: &DomainResult) -> io::Result<()> {
+        let Some(theme) = &self.theme else {
+            return writeln!(self.out, "{}", plain_label(result));
         };
 
-        execute!(io::stdout(), SetForegroundColor(color),)?;
+        let style = theme.style(result.status());
+        queue!(
+            self.out,
+            PrintStyledContent(style.content_style().apply(style.label(&result.name)))
+        )?;
+        writeln!(self.out)
+    }
+
+    fn finish(&mut self) -> io::Result<()> {
+        self.out.flush()
+    }
+}
 
-        print!("{:<width$}", result.name, width = column_width);
+/// Domains laid out in columns filling the available width
+pub struct GridRenderer<W> {
+    out: W,
+    theme: Option<Theme>,
+    column_width: usize,
+    num_columns: usize,
+    current_col: usize,
+    /// Number of sections started so far
+    sections: usize,
+}
 
-        execute!(io::stdout(), ResetColor,)?;
+impl<W: Write> GridRenderer<W> {
+    fn new(out: W, theme: Option<Theme>, width: u16) -> Self {
+        // Use static max domain length of 20 as specified
+        let max_domain_length = 20;
 
-        current_col += 1;
+        let column_width = max_domain_length + 4; // Add some padding
+        let num_columns = std::cmp::max(1, width as usize / column_width);
 
-        if current_col >= num_columns {
-            println!();
-            current_col = 0;
+        Self {
+            out,
+            theme,
+            column_width,
+            num_columns,
+            current_col: 0,
+            sections: 0,
         }
     }
+}
 
-    if current_col > 0 {
-        println!();
+impl<W: Write> Renderer for GridRenderer<W> {
+    fn section(&mut self, query: &str) -> io::Result<()> {
+        // End the previous section's last row so the heading starts a line of its own
+        if self.current_col > 0 {
+            writeln!(self.out)?;
+            self.current_col = 0;
+        }
+        write_heading(
+            &mut self.out,
+            query,
+            self.theme.is_some(),
+            self.sections == 0,
+        )?;
+        self.sections += 1;
+        Ok(())
     }
 
-    println!();
+    fn render(&mut self, result: &DomainResult) -> io::Result<()> {
+        let style = self
+            .theme
+            .as_ref()
+            .map(|theme| theme.style(result.status()));
+        let label = match style {
+            Some(style) => style.label(&result.name),
+            None => plain_label(result),
+        };
+        let length = label.chars().count();
 
-    Ok(())
+        // A name too long for one column spans as many as it needs, so it never runs into the
+        // next one, moving to a new row if it doesn't fit on this one
+        let span = (length + 1).div_ceil(self.column_width);
+        if self.current_col > 0 && self.current_col + span > self.num_columns {
+            writeln!(self.out)?;
+            self.current_col = 0;
+        }
+
+        // Pad outside the styled content so underlines don't run across the gap
+        match style {
+            Some(style) => queue!(
+                self.out,
+                PrintStyledContent(style.content_style().apply(label)),
+            )?,
+            None => write!(self.out, "{}", label)?,
+        }
+
+        // A name needing the whole row gets it to itself, unpadded so it doesn't wrap
+        if span >= self.num_columns {
+            writeln!(self.out)?;
+            self.current_col = 0;
+            return self.out.flush();
+        }
+        let padding = span * self.column_width - length;
+        write!(self.out, "{:padding$}", "")?;
+
+        self.current_col += span;
+
+        if self.current_col >= self.num_columns {
+            writeln!(self.out)?;
+            self.current_col = 0;
+        }
+
+        // Show each cell as soon as it arrives rather than a row at a time
+        self.out.flush()
+    }
+
+    fn finish(&mut self) -> io::Result<()> {
+        if self.current_col > 0 {
+            writeln!(self.out)?;
+        }
+
+        writeln!(self.out)?;
+
+        self.out.flush()
+    }
+}
+
+/// A JSON array with one object per line
+pub struct JsonRenderer<W> {
+    out: W,
+    first: bool,
+}
+
+impl<W: Write> Renderer for JsonRenderer<W> {
+    fn begin(&mut self) -> io::Result<()> {
+        // Print opening bracket for JSON array
+        writeln!(self.out, "[")
+    }
+
+    fn render(&mut self, result: &DomainResult) -> io::Result<()> {
+        // Add comma after previous item
+        if !self.first {
+            writeln!(self.out, ",")?;
+        }
+
+        // Convert domain result to JSON
+        let json_str = serde_json::to_string(result).unwrap_or_default();
+        write!(self.out, "{}", json_str)?;
+
+        self.first = false;
+
+        Ok(())
+    }
+
+    fn finish(&mut self) -> io::Result<()> {
+        // Add newline after the last item
+        if !self.first {
+            writeln!(self.out)?;
+        }
+
+        // Print closing bracket for JSON array
+        writeln!(self.out, "]")?;
+
+        self.out.flush()
+    }
+}
+
+/// One line per domain rendered from a user supplied template
+pub struct TemplateRenderer<W> {
+    out: W,
+    template: Template,
+}
+
+impl<W: Write> Renderer for TemplateRenderer<W> {
+    fn render(&mut self, result: &DomainResult) -> io::Result<()> {
+        writeln!(self.out, "{}", self.template.render(result))
+    }
+
+    fn finish(&mut self) -> io::Result<()> {
+        self.out.flush()
+    }
+}
+
+fn status_text(status: DomainStatus) -> &'static str {
+    match status {
+        DomainStatus::Available => "Available",
+        DomainStatus::Taken => "Taken",
+        DomainStatus::Premium => "Premium",
+        DomainStatus::Unknown => "Unknown",
+    }
+}
+
+/// A Markdown table with a status column, and a query column for several queries
+pub struct MarkdownRenderer<W> {
+    out: W,
+    show_query: bool,
 }
 
-/// Display domain search results in JSON format
-pub async fn display_json<S>(stream: S) -> io::Result<()>
+impl<W: Write> Renderer for MarkdownRenderer<W> {
+    fn begin(&mut self) -> io::Result<()> {
+        if self.show_query {
+            writeln!(self.out, "| Domain | Status | Query |")?;
+            writeln!(self.out, "| --- | --- | --- |")
+        } else {
+            writeln!(self.out, "| Domain | Status |")?;
+            writeln!(self.out, "| --- | --- |")
+        }
+    }
+
+    fn render(&mut self, result: &DomainResult) -> io::Result<()> {
+        write!(
+            self.out,
+            "| {} | {} |",
+            result.name.replace('|', "\\|"),
+            status_text(result.status())
+        )?;
+        if self.show_query {
+            let query = result.query.as_deref().unwrap_or_default();
+            write!(self.out, " {} |", query.replace('|', "\\|"))?;
+        }
+        writeln!(self.out)
+    }
+
+    fn finish(&mut self) -> io::Result<()> {
+        self.out.flush()
+    }
+}
+
+fn escape_html(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+const HTML_STYLE: &str = r#"body { font-family: -apple-system, BlinkMacSystemFont, "Segoe UI", Helvetica, Arial, sans-serif; margin: 2rem auto; max-width: 56rem; padding: 0 1rem; color: #1f2328; }
+h1 { font-size: 1.5rem; margin-bottom: 0.25rem; }
+.meta { color: #59636e; margin-top: 0; }
+table { border-collapse: collapse; width: 100%; }
+th, td { text-align: left; padding: 0.5rem 0.75rem; border-bottom: 1px solid #d1d9e0; }
+th { cursor: pointer; user-select: none; background: #f6f8fa; }
+th[data-order="asc"]::after { content: " \25B2"; }
+th[data-order="desc"]::after { content: " \25BC"; }
+.badge { display: inline-block; padding: 0.1rem 0.6rem; border-radius: 1rem; font-size: 0.85rem; font-weight: 600; }
+.available { background: #dafbe1; color: #1a7f37; }
+.taken { background: #ffebe9; color: #cf222e; }
+.premium { background: #fff8c5; color: #9a6700; }
+.unknown { background: #eaeef2; color: #59636e; }"#;
+
+const HTML_SCRIPT: &str = r#"document.querySelectorAll("th").forEach(function (th, column) {
+  th.addEventListener("click", function () {
+    var order = th.dataset.order === "asc" ? "desc" : "asc";
+    document.querySelectorAll("th").forEach(function (other) { delete other.dataset.order; });
+    th.dataset.order = order;
+    var body = document.querySelector("tbody");
+    Array.from(body.rows)
+      .sort(function (a, b) {
+        var cmp = a.cells[column].textContent.localeCompare(b.cells[column].textContent);
+        return order === "asc" ? cmp : -cmp;
+      })
+      .forEach(function (row) { body.appendChild(row); });
+  });
+});"#;
+
+/// A self-contained HTML page with sortable columns
+pub struct HtmlRenderer<W> {
+    out: W,
+    /// The search queries, joined for the page title
+    title: String,
+    show_query: bool,
+}
+
+impl<W: Write> Renderer for HtmlRenderer<W> {
+    fn begin(&mut self) -> io::Result<()> {
+        let query = escape_html(&self.title);
+        let generated = chrono::Local::now().format("%Y-%m-%d %H:%M %Z");
+
+        writeln!(self.out, "<!DOCTYPE html>")?;
+        writeln!(self.out, "<html lang=\"en\">")?;
+        writeln!(self.out, "<head>")?;
+        writeln!(self.out, "<meta charset=\"utf-8\">")?;
+        writeln!(self.out, "<title>Namekit results: {}</title>", query)?;
+        writeln!(self.out, "<style>\n{}\n</style>", HTML_STYLE)?;
+        writeln!(self.out, "</head>")?;
+        writeln!(self.out, "<body>")?;
+        writeln!(self.out, "<h1>{}</h1>", query)?;
+        writeln!(
+            self.out,
+            "<p class=\"meta\">Domain search generated by Namekit on {}</p>",
+            generated
+        )?;
+        writeln!(self.out, "<table>")?;
+        let query_header = if self.show_query {
+            "<th>Query</th>"
+        } else {
+            ""
+        };
+        writeln!(
+            self.out,
+            "<thead><tr><th>Domain</th><th>TLD</th><th>Status</th>{}</tr></thead>",
+            query_header
+        )?;
+        writeln!(self.out, "<tbody>")
+    }
+
+    fn render(&mut self, result: &DomainResult) -> io::Result<()> {
+        let status = result.status();
+        let tld = result.name.split_once('.').map_or("", |(_, tld)| tld);
+        let query_cell = if self.show_query {
+            let query = result.query.as_deref().unwrap_or_default();
+            format!("<td>{}</td>", escape_html(query))
+        } else {
+            String::new()
+        };
+        writeln!(
+            self.out,
+            "<tr><td>{}</td><td>{}</td><td><span class=\"badge {}\">{}</span></td>{}</tr>",
+            escape_html(&result.name),
+            escape_html(tld),
+            status_text(status).to_lowercase(),
+            status_text(status),
+            query_cell
+        )
+    }
+
+    fn finish(&mut self) -> io::Result<()> {
+        writeln!(self.out, "</tbody>")?;
+        writeln!(self.out, "</table>")?;
+        writeln!(self.out, "<script>\n{}\n</script>", HTML_SCRIPT)?;
+        writeln!(self.out, "</body>")?;
+        writeln!(self.out, "</html>")?;
+
+        self.out.flush()
+    }
+}
+
+/// Display domain search results, feeding each one to every renderer so the same
+/// stream can be shown on screen and written to a file
+///
+/// Results rejected by `filter` are counted but not rendered, and a summary of the
+/// search is printed to `footer` once the stream ends. With a `sort` order, results
+/// are held back until the stream ends so they can be sorted. With `sections`, they are
+/// also held back and then grouped under a section for each of those queries, in order.
+pub async fn display_results<S>(
+    stream: S,
+    mut renderers: Vec<Box<dyn Renderer>>,
+    filter: &Filter,
+    sort: Option<SortOrder>,
+    sections: Option<&[String]>,
+    footer: Footer,
+) -> io::Result<()>
 where
     S: Stream<Item = DomainResult> + Unpin,
 {
+    let started = Instant::now();
+    let mut summary = Summary::default();
+    let mut held_back = Vec::new();
     let mut stream = Box::pin(stream);
 
-    // Print opening bracket for JSON array
-    println!("[");
-
-    let mut first = true;
+    for renderer in &mut renderers {
+        renderer.begin()?;
+    }
 
-    // Print each result as a JSON object on a single line
     while let Some(result) = stream.next().await {
-        // Add comma after previous item
-        if !first {
-            println!(",");
+        let shown = filter.shows(&result);
+        summary.record(&result, shown);
+
+        if !shown {
+            continue;
         }
 
-        // Convert domain result to JSON
-        let json_str = serde_json::to_string(&result).unwrap_or_default();
-        print!("{}", json_str);
+        if sort.is_some() || sections.is_some() {
+            held_back.push(result);
+            continue;
+        }
 
-        if first {
-            first = false;
+        for renderer in &mut renderers {
+            renderer.render(&result)?;
         }
     }
 
-    // Add newline after the last item
-    if !first {
-        println!();
+    if let Some(sort) = sort {
+        sort.sort(&mut held_back);
+    }
+    match sections {
+        Some(queries) => {
+            for query in queries {
+                for renderer in &mut renderers {
+                    renderer.section(query)?;
+                }
+                let in_section = held_back
+                    .iter()
+                    .filter(|result| result.query.as_ref() == Some(query));
+                for result in in_section {
+                    for renderer in &mut renderers {
+                        renderer.render(result)?;
+                    }
+                }
+            }
+        }
+        None => {
+            for result in &held_back {
+                for renderer in &mut renderers {
+                    renderer.render(result)?;
+                }
+            }
+        }
     }
 
-    // Print closing bracket for JSON array
-    println!("]");
+    for renderer in &mut renderers {
+        renderer.finish()?;
+    }
+
+    summary.elapsed = started.elapsed();
+
+    match footer {
+        Footer::None => {}
+        Footer::Stdout => println!("{}", summary),
+        Footer::Stderr => eprintln!("{}", summary),
+    }
 
     Ok(())
 }
 
-/// Display domain search results based on the specified output mode
-pub async fn display_results<S>(stream: S, mode: OutputMode) -> io::Result<()>
-where
-    S: Stream<Item = DomainResult> + Unpin,
-{
-    match mode {
-        OutputMode::List => display_list(stream).await,
-        OutputMode::Grid => display_grid(stream).await,
-        OutputMode::Json => display_json(stream).await,
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::replay_domains;
+    use crate::recording::Recording;
+    use futures_util::stream;
+    use std::cell::RefCell;
+    use std::fs;
+    use std::rc::Rc;
+
+    /// A writer the test can read back after the renderer has taken ownership of it
+    #[derive(Clone, Default)]
+    struct SharedBuffer(Rc<RefCell<Vec<u8>>>);
+
+    impl Write for SharedBuffer {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.borrow_mut().write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl SharedBuffer {
+        fn contents(&self) -> String {
+            String::from_utf8(self.0.borrow().clone()).unwrap()
+        }
+    }
+
+    /// Replay a fixture through `display_results` with a single uncoloured renderer
+    async fn render_fixture(
+        name: &str,
+        mode: OutputMode,
+        filter: Filter,
+        sort: Option<SortOrder>,
+    ) -> String {
+        let path = format!("{}/tests/fixtures/{}", env!("CARGO_MANIFEST_DIR"), name);
+        let recording = Recording::read(Path::new(&path)).unwrap();
+        let query = recording.header.query.clone();
+
+        let buffer = SharedBuffer::default();
+        let renderers = vec![renderer(mode, buffer.clone(), None, &[query], 80)];
+        display_results(
+            replay_domains(recording),
+            renderers,
+            &filter,
+            sort,
+            None,
+            Footer::None,
+        )
+        .await
+        .unwrap();
+        buffer.contents()
+    }
+
+    const SHOW_ALL: Filter = Filter {
+        show_taken: true,
+        hide_premium: false,
+    };
+
+    #[tokio::test]
+    async fn list_marks_each_state_without_colour() {
+        let out = render_fixture("search_basic.ndjson", OutputMode::List, SHOW_ALL, None).await;
+        assert_eq!(out, "foo.com\nfoo.dev x\nfoo.app *\nfoo.io ?\n");
+    }
+
+    #[tokio::test]
+    async fn default_filter_hides_taken_and_unknown() {
+        let filter = Filter {
+            show_taken: false,
+            hide_premium: false,
+        };
+        let out = render_fixture("search_basic.ndjson", OutputMode::List, filter, None).await;
+        assert_eq!(out, "foo.com\nfoo.app *\n");
+    }
+
+    #[tokio::test]
+    async fn hide_premium_drops_premium_results() {
+        let filter = Filter {
+            show_taken: true,
+            hide_premium: true,
+        };
+        let out = render_fixture("search_basic.ndjson", OutputMode::List, filter, None).await;
+        assert_eq!(out, "foo.com\nfoo.dev x\nfoo.io ?\n");
+    }
+
+    #[tokio::test]
+    async fn sorts_by_name_once_the_stream_ends() {
+        let sort = Some(SortOrder::Name);
+        let out = render_fixture("search_basic.ndjson", OutputMode::List, SHOW_ALL, sort).await;
+        assert_eq!(out, "foo.app *\nfoo.com\nfoo.dev x\nfoo.io ?\n");
+    }
+
+    #[tokio::test]
+    async fn json_is_a_valid_array() {
+        let out = render_fixture("search_malformed.ndjson", OutputMode::Json, SHOW_ALL, None).await;
+        let parsed: Vec<DomainResult> = serde_json::from_str(&out).unwrap();
+
+        let names: Vec<&str> = parsed.iter().map(|r| r.name.as_str()).collect();
+        assert_eq!(names, ["brewhaus.com", "roastery.app", "cafecito.com"]);
+    }
+
+    #[tokio::test]
+    async fn json_without_results_is_an_empty_array() {
+        let out = render_fixture("rate_limited.ndjson", OutputMode::Json, SHOW_ALL, None).await;
+        assert_eq!(out, "[\n]\n");
+        assert!(
+            serde_json::from_str::<Vec<DomainResult>>(&out)
+                .unwrap()
+                .is_empty()
+        );
+    }
+
+    #[tokio::test]
+    async fn html_report_includes_the_recorded_query() {
+        let out = render_fixture("search_malformed.ndjson", OutputMode::Html, SHOW_ALL, None).await;
+        assert!(out.contains("coffee shop"));
+        assert!(out.contains("cafecito.com"));
+    }
+
+    /// One result in each state, then names wider than a grid column
+    fn sample_results() -> Vec<DomainResult> {
+        let result = |name: &str, available, premium| {
+            DomainResult::new_with_premium(name.to_string(), available, premium)
+        };
+        vec![
+            result("foo.com", Some(true), false),
+            result("foo.dev", Some(false), false),
+            result("foo.app", Some(true), true),
+            result("foo.io", None, false),
+            result(
+                "the-longest-name-anyone-could-want-for-a-shop.com",
+                Some(true),
+                false,
+            ),
+            result("foo.ai", Some(true), false),
+            result("coffee-and-cake-corner.com", Some(false), false),
+        ]
+    }
+
+    /// Render `results` unfiltered through one renderer, as `width` columns
+    async fn render(
+        results: Vec<DomainResult>,
+        mode: OutputMode,
+        theme: Option<&Theme>,
+        width: u16,
+    ) -> String {
+        let buffer = SharedBuffer::default();
+        let queries = ["foo".to_string()];
+        let renderers = vec![renderer(mode, buffer.clone(), theme, &queries, width)];
+        display_results(
+            stream::iter(results),
+            renderers,
+            &SHOW_ALL,
+            None,
+            None,
+            Footer::None,
+        )
+        .await
+        .unwrap();
+        buffer.contents()
+    }
+
+    /// Compare `output` with the snapshot in `tests/snapshots/<name>.txt`. Run the tests with
+    /// `UPDATE_SNAPSHOTS=1` to write the snapshots instead, then review the diff.
+    fn assert_snapshot(name: &str, output: &str) {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("tests/snapshots")
+            .join(format!("{}.txt", name));
+        // Escapes are written out so colour codes are readable in the file and in diffs
+        let output = output.replace('\x1b', "\\e");
+
+        if env::var_os("UPDATE_SNAPSHOTS").is_some() {
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(&path, &output).unwrap();
+            return;
+        }
+
+        let expected = fs::read_to_string(&path).unwrap_or_else(|_| {
+            panic!(
+                "{} is missing; run with UPDATE_SNAPSHOTS=1 to create it",
+                path.display()
+            )
+        });
+        assert_eq!(
+            output,
+            expected,
+            "output differs from {}; run with UPDATE_SNAPSHOTS=1 if the change is intended",
+            path.display()
+        );
+    }
+
+    #[tokio::test]
+    async fn list_snapshots() {
+        let theme = Theme::preset("default").unwrap();
+        let plain = render(sample_results(), OutputMode::List, None, 80).await;
+        let coloured = render(sample_results(), OutputMode::List, Some(&theme), 80).await;
+        let empty = render(Vec::new(), OutputMode::List, Some(&theme), 80).await;
+
+        assert_snapshot("list_plain", &plain);
+        assert_snapshot("list_color", &coloured);
+        assert_eq!(empty, "");
+    }
+
+    #[tokio::test]
+    async fn grid_snapshots() {
+        let theme = Theme::preset("default").unwrap();
+        let plain = render(sample_results(), OutputMode::Grid, None, 80).await;
+        let coloured = render(sample_results(), OutputMode::Grid, Some(&theme), 80).await;
+        let narrow = render(sample_results(), OutputMode::Grid, None, 30).await;
+        let empty = render(Vec::new(), OutputMode::Grid, Some(&theme), 80).await;
+
+        assert_snapshot("grid_plain", &plain);
+        assert_snapshot("grid_color", &coloured);
+        assert_snapshot("grid_narrow", &narrow);
+        assert_eq!(empty, "\n");
+    }
+
+    #[tokio::test]
+    async fn grid_never_runs_long_names_together() {
+        let out = render(sample_results(), OutputMode::Grid, None, 80).await;
+        assert!(out.contains("coffee-and-cake-corner.com x "));
+        assert!(out.contains("\nthe-longest-name-anyone-could-want-for-a-shop.com\n"));
+
+        // Padding never takes a line past the width, so the terminal doesn't wrap it. Only a
+        // name wider than the terminal can, on a row of its own.
+        for width in [80, 30] {
+            let out = render(sample_results(), OutputMode::Grid, None, width).await;
+            for line in out.lines() {
+                assert!(
+                    line.chars().count() <= width as usize || !line.contains(' '),
+                    "{:?} is too wide for {} columns",
+                    line,
+                    width
+                );
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn json_snapshots() {
+        let theme = Theme::preset("default").unwrap();
+        let plain = render(sample_results(), OutputMode::Json, None, 80).await;
+        let coloured = render(sample_results(), OutputMode::Json, Some(&theme), 80).await;
+        let empty = render(Vec::new(), OutputMode::Json, None, 80).await;
+
+        assert_snapshot("json", &plain);
+        // JSON is for other programs, so it is never coloured
+        assert_eq!(coloured, plain);
+        assert_snapshot("json_empty", &empty);
+    }
+
+    /// Render results found by two queries, grouped into sections when `sections` is set
+    async fn render_queries(mode: OutputMode, sections: bool) -> String {
+        let tagged = |name: &str, query: &str| DomainResult {
+            query: Some(query.to_string()),
+            ..DomainResult::new_with_premium(name.to_string(), Some(true), false)
+        };
+        let results = vec![
+            tagged("coffee.com", "coffee"),
+            tagged("tea.com", "tea | chai"),
+            tagged("coffee.dev", "coffee"),
+        ];
+        let queries = ["coffee".to_string(), "tea | chai".to_string()];
+
+        let buffer = SharedBuffer::default();
+        let renderers = vec![renderer(mode, buffer.clone(), None, &queries, 80)];
+        display_results(
+            stream::iter(results),
+            renderers,
+            &SHOW_ALL,
+            None,
+            sections.then_some(&queries[..]),
+            Footer::None,
+        )
+        .await
+        .unwrap();
+        buffer.contents()
+    }
+
+    #[tokio::test]
+    async fn sections_group_results_under_their_query() {
+        let list = render_queries(OutputMode::List, true).await;
+        assert_eq!(
+            list,
+            "== coffee ==\ncoffee.com\ncoffee.dev\n\n== tea | chai ==\ntea.com\n"
+        );
+
+        let grid = render_queries(OutputMode::Grid, true).await;
+        let lines: Vec<&str> = grid.lines().map(str::trim_end).collect();
+        assert_eq!(
+            lines,
+            [
+                "== coffee ==",
+                "coffee.com              coffee.dev",
+                "",
+                "== tea | chai ==",
+                "tea.com",
+                ""
+            ]
+        );
+
+        // Without sections, results are shown as they arrive
+        let list = render_queries(OutputMode::List, false).await;
+        assert_eq!(list, "coffee.com\ntea.com\ncoffee.dev\n");
+    }
+
+    #[tokio::test]
+    async fn reports_show_the_query_of_each_result() {
+        let markdown = render_queries(OutputMode::Markdown, false).await;
+        assert!(markdown.starts_with("| Domain | Status | Query |\n"));
+        assert!(markdown.contains("| tea.com | Available | tea \\| chai |\n"));
+
+        let html = render_queries(OutputMode::Html, false).await;
+        assert!(html.contains("<title>Namekit results: coffee, tea | chai</title>"));
+        assert!(html.contains("<td>tea | chai</td></tr>"));
     }
 }
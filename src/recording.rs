@@ -0,0 +1,181 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+/// Format version written in the first line of every recording
+const RECORDING_VERSION: u32 = 1;
+
+#[derive(Debug)]
+pub enum RecordingError {
+    IoError(io::Error),
+    InvalidHeader(serde_json::Error),
+    Empty,
+    UnsupportedVersion(u32),
+}
+
+impl fmt::Display for RecordingError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RecordingError::IoError(e) => write!(f, "IO error: {}", e),
+            RecordingError::InvalidHeader(e) => {
+                write!(f, "The first line is not a namekit recording header: {}", e)
+            }
+            RecordingError::Empty => write!(f, "The recording is empty"),
+            RecordingError::UnsupportedVersion(version) => write!(
+                f,
+                "Recording format version {} is not supported (expected {})",
+                version, RECORDING_VERSION
+            ),
+        }
+    }
+}
+
+impl From<io::Error> for RecordingError {
+    fn from(err: io::Error) -> Self {
+        RecordingError::IoError(err)
+    }
+}
+
+impl std::error::Error for RecordingError {}
+
+/// First line of a recording: the request that was made and how the server answered
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RecordingHeader {
+    pub namekit_recording: u32,
+    pub recorded_at: String,
+    pub endpoint: String,
+    pub query: String,
+    pub mode: String,
+    pub tlds: String,
+    pub status: u16,
+    /// Response headers with cookies redacted; requests carry the API token, so their
+    /// headers are never saved
+    #[serde(default)]
+    pub headers: BTreeMap<String, String>,
+}
+
+impl RecordingHeader {
+    pub fn new(endpoint: &str, query: &str, mode: &str, tlds: &str, status: u16) -> Self {
+        Self {
+            namekit_recording: RECORDING_VERSION,
+            recorded_at: chrono::Local::now().to_rfc3339(),
+            endpoint: endpoint.to_string(),
+            query: query.to_string(),
+            mode: mode.to_string(),
+            tlds: tlds.to_string(),
+            status,
+            headers: BTreeMap::new(),
+        }
+    }
+}
+
+/// Writes a recording: a JSON header line followed by the response body byte for byte
+pub struct Recorder {
+    out: BufWriter<File>,
+}
+
+impl Recorder {
+    /// Create the recording file up front, so a bad path is reported before any request
+    pub fn create(path: &Path) -> io::Result<Self> {
+        Ok(Self {
+            out: BufWriter::new(File::create(path)?),
+        })
+    }
+
+    pub fn write_header(&mut self, header: &RecordingHeader) -> io::Result<()> {
+        serde_json::to_writer(&mut self.out, header)?;
+        writeln!(self.out)?;
+        self.out.flush()
+    }
+
+    /// Append a chunk of the response body as it arrived, before it is split into lines,
+    /// so blank lines and lines the decoder rejects are kept too. Each chunk is flushed so
+    /// an interrupted search still leaves a usable recording.
+    pub fn write_chunk(&mut self, chunk: &[u8]) -> io::Result<()> {
+        self.out.write_all(chunk)?;
+        self.out.flush()
+    }
+}
+
+/// A recording read back from disk
+#[derive(Debug)]
+pub struct Recording {
+    pub header: RecordingHeader,
+    /// The response body, which may hold invalid UTF-8 or over-long lines
+    pub body: Vec<u8>,
+}
+
+impl Recording {
+    pub fn read(path: &Path) -> Result<Self, RecordingError> {
+        Self::parse(&fs::read(path)?)
+    }
+
+    pub fn parse(contents: &[u8]) -> Result<Self, RecordingError> {
+        if contents.is_empty() {
+            return Err(RecordingError::Empty);
+        }
+        let (header, body) = match contents.iter().position(|&b| b == b'\n') {
+            Some(newline) => (&contents[..newline], &contents[newline + 1..]),
+            None => (contents, &[][..]),
+        };
+        let header: RecordingHeader =
+            serde_json::from_slice(header).map_err(RecordingError::InvalidHeader)?;
+        if header.namekit_recording != RECORDING_VERSION {
+            return Err(RecordingError::UnsupportedVersion(header.namekit_recording));
+        }
+
+        Ok(Self {
+            header,
+            body: body.to_vec(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn recorder_output_reads_back() {
+        let path =
+            std::env::temp_dir().join(format!("namekit-recording-{}.ndjson", std::process::id()));
+
+        let mut recorder = Recorder::create(&path).unwrap();
+        let header = RecordingHeader::new(
+            "https://api.namekit.app/domains/stream",
+            "foo",
+            "tld",
+            "com",
+            200,
+        );
+        recorder.write_header(&header).unwrap();
+        recorder.write_chunk(br#"{"domain": "foo.com", "#).unwrap();
+        recorder
+            .write_chunk(b"\"available\": true}\n\n\xff\xfe\n")
+            .unwrap();
+        recorder.write_chunk(b"not json").unwrap();
+        drop(recorder);
+
+        let recording = Recording::read(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(recording.header.query, "foo");
+        assert_eq!(recording.header.status, 200);
+        assert_eq!(
+            recording.body,
+            b"{\"domain\": \"foo.com\", \"available\": true}\n\n\xff\xfe\nnot json"
+        );
+    }
+
+    #[test]
+    fn rejects_files_that_are_not_recordings() {
+        assert!(matches!(Recording::parse(b""), Err(RecordingError::Empty)));
+        assert!(matches!(
+            Recording::parse(b"{\"domain\": \"foo.com\"}\n"),
+            Err(RecordingError::InvalidHeader(_))
+        ));
+    }
+}
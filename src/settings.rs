@@ -0,0 +1,558 @@
+use crate::client;
+use crate::config::{self, Config, ConfigError, NetworkSettings, SearchDefaults, mask_token};
+use crate::output;
+use crate::theme::{self, StyleConfig};
+use std::path::{self, Path};
+
+/// Type of value a setting accepts, used to validate `config set`
+pub enum Kind {
+    Text,
+    Secret,
+    Bool,
+    Choice(&'static [&'static str]),
+    Color,
+    Profile,
+    Tlds,
+    Url,
+    Seconds,
+    Proxy,
+    File,
+}
+
+impl Kind {
+    fn expected(&self) -> String {
+        match self {
+            Kind::Text | Kind::Secret => "a non-empty value".to_string(),
+            Kind::Bool => "true or false".to_string(),
+            Kind::Choice(choices) => format!("one of: {}", choices.join(", ")),
+            Kind::Color => {
+                "a colour name such as 'dark_blue', a '#rrggbb' value or 'none'".to_string()
+            }
+            Kind::Profile => "the name of an existing profile".to_string(),
+            Kind::Url => "an http:// or https:// URL such as 'https://api.namekit.app'".to_string(),
+            Kind::Tlds => "a comma separated list of TLDs such as 'com,dev,app'".to_string(),
+            Kind::Seconds => "a whole number of seconds, or 0 for no limit".to_string(),
+            Kind::Proxy => {
+                "an http:// or https:// URL such as 'http://proxy.example.com:8080'".to_string()
+            }
+            Kind::File => "the path of an existing file".to_string(),
+        }
+    }
+}
+
+/// A setting that can be read and written with `config get/set/unset`
+pub struct Setting {
+    pub key: &'static str,
+    pub kind: Kind,
+    pub description: &'static str,
+}
+
+impl Setting {
+    /// `value` as it may be shown back to the user, with secrets masked
+    pub fn display_value(&self, value: &str) -> String {
+        match self.kind {
+            Kind::Secret => mask_token(value),
+            _ => value.to_string(),
+        }
+    }
+}
+
+macro_rules! theme_state_settings {
+    ($state:literal) => {
+        [
+            Setting {
+                key: concat!("theme.", $state, ".color"),
+                kind: Kind::Color,
+                description: concat!("Colour of ", $state, " domains"),
+            },
+            Setting {
+                key: concat!("theme.", $state, ".bold"),
+                kind: Kind::Bool,
+                description: concat!("Show ", $state, " domains in bold"),
+            },
+            Setting {
+                key: concat!("theme.", $state, ".underline"),
+                kind: Kind::Bool,
+                description: concat!("Underline ", $state, " domains"),
+            },
+            Setting {
+                key: concat!("theme.", $state, ".glyph"),
+                kind: Kind::Text,
+                description: concat!("Glyph shown in front of ", $state, " domains"),
+            },
+        ]
+    };
+}
+
+const GENERAL_SETTINGS: [Setting; 17] = [
+    Setting {
+        key: "current_profile",
+        kind: Kind::Profile,
+        description: "Profile used when --profile is not given",
+    },
+    Setting {
+        key: "api_token",
+        kind: Kind::Secret,
+        description: "API token for the selected profile",
+    },
+    Setting {
+        key: "api_server",
+        kind: Kind::Url,
+        description: "API server URL for the selected profile",
+    },
+    Setting {
+        key: "defaults.output",
+        kind: Kind::Choice(&output::OUTPUT_FORMATS),
+        description: "Output format used when --output is not given",
+    },
+    Setting {
+        key: "defaults.show_taken",
+        kind: Kind::Bool,
+        description: "Show taken domains unless --no-show-taken is given",
+    },
+    Setting {
+        key: "defaults.hide_premium",
+        kind: Kind::Bool,
+        description: "Hide premium domains unless --no-hide-premium is given",
+    },
+    Setting {
+        key: "defaults.tlds",
+        kind: Kind::Tlds,
+        description: "TLDs checked when --tlds is not given",
+    },
+    Setting {
+        key: "defaults.sort",
+        kind: Kind::Choice(&output::SORT_ORDERS),
+        description: "Result order used when --sort is not given",
+    },
+    Setting {
+        key: "network.connect_timeout",
+        kind: Kind::Seconds,
+        description: "Seconds to wait for a connection to the API server",
+    },
+    Setting {
+        key: "network.timeout",
+        kind: Kind::Seconds,
+        description: "Seconds a whole search may take (no limit by default)",
+    },
+    Setting {
+        key: "network.idle_timeout",
+        kind: Kind::Seconds,
+        description: "Seconds to wait for the server to answer or send the next result",
+    },
+    Setting {
+        key: "network.proxy",
+        kind: Kind::Proxy,
+        description: "Proxy for API requests when HTTPS_PROXY is not set",
+    },
+    Setting {
+        key: "network.no_proxy",
+        kind: Kind::Text,
+        description: "Comma separated hosts reached without the proxy when NO_PROXY is not set",
+    },
+    Setting {
+        key: "network.ca_cert",
+        kind: Kind::File,
+        description: "PEM file of extra root certificates to trust",
+    },
+    Setting {
+        key: "network.client_cert",
+        kind: Kind::File,
+        description: "PEM client certificate presented to the API server",
+    },
+    Setting {
+        key: "network.client_key",
+        kind: Kind::File,
+        description: "PEM (PKCS #8) private key of the client certificate",
+    },
+    Setting {
+        key: "theme.preset",
+        kind: Kind::Choice(&theme::PRESETS),
+        description: "Built-in theme the theme settings below are applied on top of",
+    },
+];
+
+const THEME_SETTINGS: [[Setting; 4]; 4] = [
+    theme_state_settings!("available"),
+    theme_state_settings!("taken"),
+    theme_state_settings!("premium"),
+    theme_state_settings!("unknown"),
+];
+
+/// Every setting, in the order they are listed to the user
+pub fn all() -> impl Iterator<Item = &'static Setting> {
+    GENERAL_SETTINGS
+        .iter()
+        .chain(THEME_SETTINGS.iter().flatten())
+}
+
+/// Find the setting for `key`, suggesting the closest match for typos
+pub fn lookup(key: &str) -> Result<&'static Setting, ConfigError> {
+    if let Some(setting) = all().find(|setting| setting.key == key) {
+        return Ok(setting);
+    }
+
+    let suggestion = all()
+        .map(|setting| (edit_distance(key, setting.key), setting.key))
+        .filter(|(distance, _)| *distance <= 3)
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, key)| key);
+
+    Err(ConfigError::UnknownKey(key.to_string(), suggestion))
+}
+
+/// Read a setting, or `None` if it isn't set. Tokens are masked.
+pub fn get(config: &Config, key: &str) -> Result<Option<String>, ConfigError> {
+    let setting = lookup(key)?;
+
+    let value = match setting.key {
+        "current_profile" => Some(config.current_profile.clone()),
+        "api_token" => config
+            .profiles
+            .get(config.active_profile_name())
+            .and_then(|profile| match &profile.api_token {
+                Some(token) => Some(mask_token(token)),
+                None if profile.token_encrypted => Some("Encrypted".to_string()),
+                None => None,
+            }),
+        "api_server" => config
+            .profiles
+            .get(config.active_profile_name())
+            .and_then(|profile| profile.api_server.clone()),
+        "theme.preset" => config.theme.as_ref().and_then(|t| t.preset.clone()),
+        key if key.starts_with("defaults.") => {
+            let defaults = config.defaults.as_ref();
+            match key {
+                "defaults.output" => defaults.and_then(|d| d.output.clone()),
+                "defaults.show_taken" => defaults.and_then(|d| d.show_taken.map(|v| v.to_string())),
+                "defaults.hide_premium" => {
+                    defaults.and_then(|d| d.hide_premium.map(|v| v.to_string()))
+                }
+                "defaults.tlds" => defaults.and_then(|d| d.tlds.clone()),
+                _ => defaults.and_then(|d| d.sort.clone()),
+            }
+        }
+        key if key.starts_with("network.") => {
+            let network = config.network.as_ref();
+            let path = |path: &Path| path.display().to_string();
+            match key {
+                "network.connect_timeout" => {
+                    network.and_then(|n| n.connect_timeout.map(|v| v.to_string()))
+                }
+                "network.timeout" => network.and_then(|n| n.timeout.map(|v| v.to_string())),
+                "network.idle_timeout" => {
+                    network.and_then(|n| n.idle_timeout.map(|v| v.to_string()))
+                }
+                "network.proxy" => network.and_then(|n| n.proxy.clone()),
+                "network.no_proxy" => network.and_then(|n| n.no_proxy.clone()),
+                "network.ca_cert" => network.and_then(|n| n.ca_cert.as_deref().map(path)),
+                "network.client_cert" => network.and_then(|n| n.client_cert.as_deref().map(path)),
+                _ => network.and_then(|n| n.client_key.as_deref().map(path)),
+            }
+        }
+        key => {
+            let (state, attribute) = theme_key(key);
+            let style = config.theme.as_ref().and_then(|t| match state {
+                "available" => t.available.as_ref(),
+                "taken" => t.taken.as_ref(),
+                "premium" => t.premium.as_ref(),
+                _ => t.unknown.as_ref(),
+            });
+            style.and_then(|style| match attribute {
+                "color" => style.color.clone(),
+                "bold" => style.bold.map(|b| b.to_string()),
+                "underline" => style.underline.map(|u| u.to_string()),
+                _ => style.glyph.clone(),
+            })
+        }
+    };
+
+    Ok(value)
+}
+
+/// Validate `value` and store it. The caller saves the config.
+pub fn set(config: &mut Config, key: &str, value: &str) -> Result<(), ConfigError> {
+    let setting = lookup(key)?;
+    let invalid = || ConfigError::InvalidValue {
+        key: setting.key.to_string(),
+        value: value.to_string(),
+        expected: setting.kind.expected(),
+    };
+
+    match &setting.kind {
+        Kind::Text | Kind::Secret if value.is_empty() => return Err(invalid()),
+        Kind::Bool if parse_bool(value).is_none() => return Err(invalid()),
+        Kind::Choice(choices) if !choices.contains(&value) => return Err(invalid()),
+        Kind::Color if theme::parse_color(value).is_err() => return Err(invalid()),
+        Kind::Profile if !config.profiles.contains_key(value) => return Err(invalid()),
+        Kind::Tlds if !valid_tlds(value) => return Err(invalid()),
+        Kind::Url if config::parse_api_server(value).is_err() => return Err(invalid()),
+        Kind::Seconds if value.parse::<u64>().is_err() => return Err(invalid()),
+        Kind::Proxy if client::parse_proxy(value).is_err() => return Err(invalid()),
+        Kind::File if !Path::new(value).is_file() => return Err(invalid()),
+        _ => {}
+    }
+
+    match setting.key {
+        "current_profile" => config.current_profile = value.to_string(),
+        "api_token" => config.set_plain_token(value.to_string())?,
+        "api_server" => {
+            config.active_profile_mut()?.api_server = config::parse_api_server(value).ok()
+        }
+        "theme.preset" => config.theme.get_or_insert_default().preset = Some(value.to_string()),
+        key if key.starts_with("defaults.") => {
+            let defaults = config.defaults.get_or_insert_default();
+            set_default(defaults, key, Some(value));
+        }
+        key if key.starts_with("network.") => {
+            let network = config.network.get_or_insert_default();
+            set_network(network, key, Some(value));
+        }
+        key => {
+            let (state, attribute) = theme_key(key);
+            let style = theme_style_mut(config, state);
+            match attribute {
+                "color" => style.color = Some(value.to_string()),
+                "bold" => style.bold = parse_bool(value),
+                "underline" => style.underline = parse_bool(value),
+                _ => style.glyph = Some(value.to_string()),
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Remove a setting so its default applies. The caller saves the config.
+pub fn unset(config: &mut Config, key: &str) -> Result<(), ConfigError> {
+    let setting = lookup(key)?;
+
+    match setting.key {
+        "current_profile" => config.current_profile = config::DEFAULT_PROFILE.to_string(),
+        "api_token" => config.clear_token()?,
+        "api_server" => config.active_profile_mut()?.api_server = None,
+        "theme.preset" => {
+            if let Some(theme) = &mut config.theme {
+                theme.preset = None;
+            }
+        }
+        key if key.starts_with("defaults.") => {
+            if let Some(defaults) = &mut config.defaults {
+                set_default(defaults, key, None);
+            }
+        }
+        key if key.starts_with("network.") => {
+            if let Some(network) = &mut config.network {
+                set_network(network, key, None);
+            }
+        }
+        key => {
+            let (state, attribute) = theme_key(key);
+            let style = theme_style_mut(config, state);
+            match attribute {
+                "color" => style.color = None,
+                "bold" => style.bold = None,
+                "underline" => style.underline = None,
+                _ => style.glyph = None,
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Store or clear (with `None`) one of the `defaults.*` settings
+fn set_default(defaults: &mut SearchDefaults, key: &str, value: Option<&str>) {
+    match key {
+        "defaults.output" => defaults.output = value.map(str::to_string),
+        "defaults.show_taken" => defaults.show_taken = value.and_then(parse_bool),
+        "defaults.hide_premium" => defaults.hide_premium = value.and_then(parse_bool),
+        "defaults.tlds" => defaults.tlds = value.map(|tlds| tlds.replace(' ', "")),
+        _ => defaults.sort = value.map(str::to_string),
+    }
+}
+
+/// Store or clear (with `None`) one of the `network.*` settings. File paths are stored as
+/// absolute paths, so they still work from another directory.
+fn set_network(network: &mut NetworkSettings, key: &str, value: Option<&str>) {
+    let seconds = value.and_then(|value| value.parse().ok());
+    let file = value.and_then(|value| path::absolute(value).ok());
+    match key {
+        "network.connect_timeout" => network.connect_timeout = seconds,
+        "network.timeout" => network.timeout = seconds,
+        "network.idle_timeout" => network.idle_timeout = seconds,
+        "network.proxy" => network.proxy = value.map(str::to_string),
+        "network.no_proxy" => network.no_proxy = value.map(|hosts| hosts.replace(' ', "")),
+        "network.ca_cert" => network.ca_cert = file,
+        "network.client_cert" => network.client_cert = file,
+        _ => network.client_key = file,
+    }
+}
+
+fn valid_tlds(value: &str) -> bool {
+    value.split(',').all(|tld| {
+        let tld = tld.trim().trim_start_matches('.');
+        !tld.is_empty()
+            && tld
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '.')
+    })
+}
+
+/// Split `theme.<state>.<attribute>` into its state and attribute
+fn theme_key(key: &str) -> (&str, &str) {
+    let rest = key.strip_prefix("theme.").unwrap_or(key);
+    rest.split_once('.').unwrap_or((rest, ""))
+}
+
+fn theme_style_mut<'a>(config: &'a mut Config, state: &str) -> &'a mut StyleConfig {
+    let theme = config.theme.get_or_insert_default();
+    let style = match state {
+        "available" => &mut theme.available,
+        "taken" => &mut theme.taken,
+        "premium" => &mut theme.premium,
+        _ => &mut theme.unknown,
+    };
+    style.get_or_insert_default()
+}
+
+fn parse_bool(value: &str) -> Option<bool> {
+    match value.to_lowercase().as_str() {
+        "true" | "yes" | "on" | "1" => Some(true),
+        "false" | "no" | "off" | "0" => Some(false),
+        _ => None,
+    }
+}
+
+/// Levenshtein distance, used to suggest the intended key after a typo
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+
+    for (i, ca) in a.chars().enumerate() {
+        let mut current = vec![i + 1; b.len() + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(ca != *cb);
+            current[j + 1] = substitution.min(previous[j + 1] + 1).min(current[j] + 1);
+        }
+        previous = current;
+    }
+
+    previous[b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_invalid(config: &mut Config, key: &str, value: &str) {
+        assert!(
+            matches!(
+                set(config, key, value),
+                Err(ConfigError::InvalidValue { .. })
+            ),
+            "{} = {:?} should be rejected",
+            key,
+            value
+        );
+    }
+
+    #[test]
+    fn looks_up_keys_and_suggests_close_matches() {
+        assert_eq!(lookup("defaults.tlds").unwrap().key, "defaults.tlds");
+        assert!(matches!(
+            lookup("defaults.tld"),
+            Err(ConfigError::UnknownKey(_, Some("defaults.tlds")))
+        ));
+        assert!(matches!(
+            lookup("theme.avilable.colour"),
+            Err(ConfigError::UnknownKey(_, Some("theme.available.color")))
+        ));
+        assert!(matches!(
+            lookup("completely.different"),
+            Err(ConfigError::UnknownKey(_, None))
+        ));
+    }
+
+    #[test]
+    fn validates_values_by_kind() {
+        let mut config = Config::default();
+
+        assert_invalid(&mut config, "api_token", "");
+        assert_invalid(&mut config, "defaults.show_taken", "maybe");
+        assert_invalid(&mut config, "defaults.output", "xml");
+        assert_invalid(&mut config, "theme.taken.color", "blu");
+        assert_invalid(&mut config, "current_profile", "staging");
+        assert_invalid(&mut config, "defaults.tlds", "com,,dev");
+        assert_invalid(&mut config, "api_server", "ftp://api.example.com");
+        assert_invalid(&mut config, "network.timeout", "-1");
+        assert_invalid(&mut config, "network.proxy", "socks5://proxy:1080");
+        assert_invalid(&mut config, "network.ca_cert", "/no/such/ca.pem");
+
+        set(&mut config, "defaults.show_taken", "yes").unwrap();
+        set(&mut config, "defaults.tlds", "com, dev").unwrap();
+        set(&mut config, "theme.taken.color", "#ff8800").unwrap();
+        set(&mut config, "network.timeout", "30").unwrap();
+        set(&mut config, "api_server", "https://api.example.com/").unwrap();
+        assert_eq!(
+            get(&config, "defaults.show_taken").unwrap().as_deref(),
+            Some("true")
+        );
+        assert_eq!(
+            get(&config, "defaults.tlds").unwrap().as_deref(),
+            Some("com,dev")
+        );
+        assert_eq!(
+            get(&config, "theme.taken.color").unwrap().as_deref(),
+            Some("#ff8800")
+        );
+        assert_eq!(
+            get(&config, "network.timeout").unwrap().as_deref(),
+            Some("30")
+        );
+        assert_eq!(
+            get(&config, "api_server").unwrap().as_deref(),
+            Some("https://api.example.com")
+        );
+    }
+
+    #[test]
+    fn masks_secrets() {
+        let mut config = Config::default();
+        set(&mut config, "api_token", "nk_live_0123456789").unwrap();
+
+        assert_eq!(
+            get(&config, "api_token").unwrap().as_deref(),
+            Some("nk_l...6789")
+        );
+        let setting = lookup("api_token").unwrap();
+        assert_eq!(setting.display_value("nk_live_0123456789"), "nk_l...6789");
+        let setting = lookup("defaults.tlds").unwrap();
+        assert_eq!(setting.display_value("com,dev"), "com,dev");
+    }
+
+    #[test]
+    fn unset_restores_the_default() {
+        let mut config = Config::default();
+        set(&mut config, "api_token", "nk_live_0123456789").unwrap();
+        set(&mut config, "defaults.output", "json").unwrap();
+        set(&mut config, "theme.premium.glyph", "$").unwrap();
+
+        for key in ["api_token", "defaults.output", "theme.premium.glyph"] {
+            unset(&mut config, key).unwrap();
+            assert_eq!(get(&config, key).unwrap(), None, "{} is still set", key);
+        }
+
+        config
+            .profiles
+            .insert("staging".to_string(), Default::default());
+        set(&mut config, "current_profile", "staging").unwrap();
+        unset(&mut config, "current_profile").unwrap();
+        assert_eq!(config.current_profile, config::DEFAULT_PROFILE);
+
+        assert!(matches!(
+            unset(&mut config, "defaults.colour"),
+            Err(ConfigError::UnknownKey(..))
+        ));
+    }
+}
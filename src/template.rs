@@ -0,0 +1,351 @@
+use crate::domain::{DomainResult, DomainStatus};
+use std::fmt;
+
+/// Fields that can be substituted with `{field}`
+pub const FIELDS: [&str; 8] = [
+    "name",
+    "sld",
+    "tld",
+    "status",
+    "available",
+    "premium",
+    "price",
+    "query",
+];
+
+/// Flags that can be tested with `{flag?then:else}`
+pub const CONDITIONS: [&str; 4] = ["available", "taken", "premium", "unknown"];
+
+#[derive(Debug)]
+pub enum TemplateError {
+    UnknownField(String),
+    UnknownCondition(String),
+    Unterminated,
+    UnexpectedBrace,
+}
+
+impl fmt::Display for TemplateError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TemplateError::UnknownField(name) => write!(
+                f,
+                "Unknown template field '{{{}}}' (expected one of: {})",
+                name,
+                FIELDS.join(", ")
+            ),
+            TemplateError::UnknownCondition(name) => write!(
+                f,
+                "Unknown template condition '{{{}?...}}' (expected one of: {})",
+                name,
+                CONDITIONS.join(", ")
+            ),
+            TemplateError::Unterminated => write!(f, "Unterminated '{{' in template"),
+            TemplateError::UnexpectedBrace => write!(f, "Unexpected '}}' in template"),
+        }
+    }
+}
+
+impl std::error::Error for TemplateError {}
+
+#[derive(Debug)]
+enum Part {
+    Text(String),
+    Field(String),
+    Conditional {
+        condition: String,
+        then: Vec<Part>,
+        otherwise: Vec<Part>,
+    },
+}
+
+/// A user supplied line format such as `{name}\t{tld}\t{available?yes:no}`
+///
+/// `\t`, `\n` and `\\` are unescaped so templates can be passed in single quotes,
+/// and `\{`, `\}`, `\?` and `\:` produce literal characters.
+#[derive(Debug)]
+pub struct Template {
+    parts: Vec<Part>,
+}
+
+impl Template {
+    pub fn parse(source: &str) -> Result<Self, TemplateError> {
+        let mut chars = source.chars();
+        let (parts, terminator) = parse_parts(&mut chars, false)?;
+
+        match terminator {
+            None => Ok(Self { parts }),
+            Some(_) => Err(TemplateError::UnexpectedBrace),
+        }
+    }
+
+    pub fn render(&self, result: &DomainResult) -> String {
+        let mut out = String::new();
+        render_parts(&self.parts, result, &mut out);
+        out
+    }
+}
+
+type Chars<'a> = std::str::Chars<'a>;
+
+/// Parse parts until the end of input, or until an unescaped `}` or (inside a
+/// conditional) `:` which is returned as the terminator
+fn parse_parts(
+    chars: &mut Chars<'_>,
+    in_conditional: bool,
+) -> Result<(Vec<Part>, Option<char>), TemplateError> {
+    let mut parts = Vec::new();
+    let mut text = String::new();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => match chars.next() {
+                Some('t') => text.push('\t'),
+                Some('n') => text.push('\n'),
+                Some(other) => text.push(other),
+                None => text.push('\\'),
+            },
+            '{' => {
+                push_text(&mut parts, std::mem::take(&mut text));
+                parts.push(parse_placeholder(chars)?);
+            }
+            '}' => {
+                push_text(&mut parts, text);
+                return Ok((parts, Some('}')));
+            }
+            ':' if in_conditional => {
+                push_text(&mut parts, text);
+                return Ok((parts, Some(':')));
+            }
+            _ => text.push(c),
+        }
+    }
+
+    push_text(&mut parts, text);
+    Ok((parts, None))
+}
+
+fn push_text(parts: &mut Vec<Part>, text: String) {
+    if !text.is_empty() {
+        parts.push(Part::Text(text));
+    }
+}
+
+/// Parse the inside of `{...}`, after the opening brace has been consumed
+fn parse_placeholder(chars: &mut Chars<'_>) -> Result<Part, TemplateError> {
+    let mut name = String::new();
+
+    loop {
+        match chars.next() {
+            Some('}') => {
+                let name = name.trim().to_string();
+                if !FIELDS.contains(&name.as_str()) {
+                    return Err(TemplateError::UnknownField(name));
+                }
+                return Ok(Part::Field(name));
+            }
+            Some('?') => break,
+            Some(c) => name.push(c),
+            None => return Err(TemplateError::Unterminated),
+        }
+    }
+
+    let condition = name.trim().to_string();
+    if !CONDITIONS.contains(&condition.as_str()) {
+        return Err(TemplateError::UnknownCondition(condition));
+    }
+
+    let (then, terminator) = parse_parts(chars, true)?;
+    let otherwise = match terminator {
+        Some(':') => match parse_parts(chars, false)? {
+            (otherwise, Some('}')) => otherwise,
+            _ => return Err(TemplateError::Unterminated),
+        },
+        Some(_) => Vec::new(),
+        None => return Err(TemplateError::Unterminated),
+    };
+
+    Ok(Part::Conditional {
+        condition,
+        then,
+        otherwise,
+    })
+}
+
+fn render_parts(parts: &[Part], result: &DomainResult, out: &mut String) {
+    for part in parts {
+        match part {
+            Part::Text(text) => out.push_str(text),
+            Part::Field(name) => out.push_str(&field_value(name, result)),
+            Part::Conditional {
+                condition,
+                then,
+                otherwise,
+            } => {
+                let branch = if condition_holds(condition, result) {
+                    then
+                } else {
+                    otherwise
+                };
+                render_parts(branch, result, out);
+            }
+        }
+    }
+}
+
+fn field_value(name: &str, result: &DomainResult) -> String {
+    match name {
+        "name" => result.name.clone(),
+        "sld" => result
+            .name
+            .split_once('.')
+            .map_or(result.name.as_str(), |(sld, _)| sld)
+            .to_string(),
+        "tld" => result
+            .name
+            .split_once('.')
+            .map_or("", |(_, tld)| tld)
+            .to_string(),
+        "status" => match result.status() {
+            DomainStatus::Available => "available",
+            DomainStatus::Taken => "taken",
+            DomainStatus::Premium => "premium",
+            DomainStatus::Unknown => "unknown",
+        }
+        .to_string(),
+        "available" => result.available.map(|a| a.to_string()).unwrap_or_default(),
+        "premium" => result.premium.to_string(),
+        "price" => result.price.clone().unwrap_or_default(),
+        "query" => result.query.clone().unwrap_or_default(),
+        _ => String::new(),
+    }
+}
+
+fn condition_holds(condition: &str, result: &DomainResult) -> bool {
+    match condition {
+        "available" => result.available == Some(true),
+        "taken" => result.available == Some(false),
+        "premium" => result.premium,
+        "unknown" => result.available.is_none(),
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn domain(name: &str, available: Option<bool>, premium: bool) -> DomainResult {
+        DomainResult::new_with_premium(name.to_string(), available, premium)
+    }
+
+    fn render(source: &str, result: &DomainResult) -> String {
+        Template::parse(source).unwrap().render(result)
+    }
+
+    #[test]
+    fn substitutes_fields() {
+        let result = domain("foo.co.uk", Some(true), false);
+        assert_eq!(
+            render(r"{name}\t{sld}\t{tld}\t{status}\t{available}", &result),
+            "foo.co.uk\tfoo\tco.uk\tavailable\ttrue"
+        );
+        assert_eq!(
+            render("{available}|{query}", &domain("foo.io", None, false)),
+            "|"
+        );
+
+        let mut labelled = domain("foo.com", Some(true), false);
+        labelled.query = Some("coffee shop".to_string());
+        assert_eq!(render("{query}: {name}", &labelled), "coffee shop: foo.com");
+
+        let mut priced = domain("foo.dev", Some(true), false);
+        assert_eq!(render(r"{name}\t{tld}\t{price}", &priced), "foo.dev\tdev\t");
+        priced.price = Some("12.99".to_string());
+        assert_eq!(
+            render(r"{name}\t{tld}\t{price}", &priced),
+            "foo.dev\tdev\t12.99"
+        );
+    }
+
+    #[test]
+    fn nested_conditionals_pick_the_matching_branch() {
+        let template =
+            Template::parse("{available?{premium?{name} (premium):{name}}:{taken?taken:?}}")
+                .unwrap();
+        assert_eq!(
+            template.render(&domain("foo.app", Some(true), true)),
+            "foo.app (premium)"
+        );
+        assert_eq!(
+            template.render(&domain("foo.com", Some(true), false)),
+            "foo.com"
+        );
+        assert_eq!(
+            template.render(&domain("foo.dev", Some(false), false)),
+            "taken"
+        );
+        assert_eq!(template.render(&domain("foo.io", None, false)), "?");
+    }
+
+    #[test]
+    fn the_else_branch_is_optional() {
+        assert_eq!(
+            render("{name}{taken? x}", &domain("foo.dev", Some(false), false)),
+            "foo.dev x"
+        );
+        assert_eq!(
+            render("{name}{taken? x}", &domain("foo.com", Some(true), false)),
+            "foo.com"
+        );
+    }
+
+    #[test]
+    fn escapes_produce_literal_characters() {
+        let result = domain("foo.com", Some(true), false);
+        assert_eq!(render(r"\{name\}", &result), "{name}");
+        assert_eq!(
+            render(r"{available?yes\: {name}:no}", &result),
+            "yes: foo.com"
+        );
+        assert_eq!(render(r"{available?why\?}", &result), "why?");
+        assert_eq!(render(r"{name}\n\\", &result), "foo.com\n\\");
+        // Outside a conditional a colon needs no escape
+        assert_eq!(render("name: {name}", &result), "name: foo.com");
+    }
+
+    #[test]
+    fn rejects_malformed_templates() {
+        for source in [
+            "{name",
+            "{available?yes",
+            "{available?yes:no",
+            "{available?{name}",
+        ] {
+            assert!(
+                matches!(Template::parse(source), Err(TemplateError::Unterminated)),
+                "{:?}",
+                source
+            );
+        }
+        for source in ["name}", "{name}}", "{available?yes:no}}"] {
+            assert!(
+                matches!(Template::parse(source), Err(TemplateError::UnexpectedBrace)),
+                "{:?}",
+                source
+            );
+        }
+    }
+
+    #[test]
+    fn rejects_unknown_fields_and_conditions() {
+        let error = Template::parse("{name} {cost}").unwrap_err();
+        assert!(matches!(&error, TemplateError::UnknownField(field) if field == "cost"));
+        assert!(error.to_string().contains("'{cost}'"));
+        assert!(error.to_string().contains("name, sld, tld"));
+
+        assert!(matches!(
+            Template::parse("{cheap?yes}"),
+            Err(TemplateError::UnknownCondition(condition)) if condition == "cheap"
+        ));
+    }
+}
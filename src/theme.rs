@@ -0,0 +1,218 @@
+use crate::domain::DomainStatus;
+use crossterm::style::{Attribute, Color, ContentStyle};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// Names of the built-in themes selectable with `--theme`
+pub const PRESETS: [&str; 3] = ["default", "colorblind", "monochrome"];
+
+#[derive(Debug)]
+pub enum ThemeError {
+    UnknownPreset(String),
+    UnknownColor(String),
+}
+
+impl fmt::Display for ThemeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ThemeError::UnknownPreset(name) => write!(
+                f,
+                "Unknown theme preset '{}' (expected one of: {})",
+                name,
+                PRESETS.join(", ")
+            ),
+            ThemeError::UnknownColor(name) => write!(f, "Unknown theme colour '{}'", name),
+        }
+    }
+}
+
+impl std::error::Error for ThemeError {}
+
+/// Theme section of the config file: an optional base preset plus per-state overrides
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct ThemeConfig {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub preset: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub available: Option<StyleConfig>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub taken: Option<StyleConfig>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub premium: Option<StyleConfig>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub unknown: Option<StyleConfig>,
+}
+
+/// Overrides for a single state; unset fields keep the preset's value
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct StyleConfig {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub color: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bold: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub underline: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub glyph: Option<String>,
+}
+
+/// How a single domain state is drawn
+#[derive(Debug, Clone)]
+pub struct StateStyle {
+    pub color: Option<Color>,
+    pub bold: bool,
+    pub underline: bool,
+    pub glyph: Option<String>,
+}
+
+impl StateStyle {
+    fn new(color: Option<Color>, bold: bool, underline: bool, glyph: Option<&str>) -> Self {
+        Self {
+            color,
+            bold,
+            underline,
+            glyph: glyph.map(str::to_string),
+        }
+    }
+
+    fn apply(&mut self, overrides: &StyleConfig) -> Result<(), ThemeError> {
+        if let Some(color) = &overrides.color {
+            self.color = parse_color(color)?;
+        }
+        if let Some(bold) = overrides.bold {
+            self.bold = bold;
+        }
+        if let Some(underline) = overrides.underline {
+            self.underline = underline;
+        }
+        if let Some(glyph) = &overrides.glyph {
+            self.glyph = (!glyph.is_empty()).then(|| glyph.clone());
+        }
+        Ok(())
+    }
+
+    pub fn content_style(&self) -> ContentStyle {
+        let mut style = ContentStyle::new();
+        style.foreground_color = self.color;
+        if self.bold {
+            style.attributes.set(Attribute::Bold);
+        }
+        if self.underline {
+            style.attributes.set(Attribute::Underlined);
+        }
+        style
+    }
+
+    /// The domain name with this state's glyph prefixed, if it has one
+    pub fn label(&self, name: &str) -> String {
+        match &self.glyph {
+            Some(glyph) => format!("{} {}", glyph, name),
+            None => name.to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Theme {
+    pub available: StateStyle,
+    pub taken: StateStyle,
+    pub premium: StateStyle,
+    pub unknown: StateStyle,
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Self {
+            available: StateStyle::new(Some(Color::Green), false, false, None),
+            taken: StateStyle::new(Some(Color::Red), false, false, None),
+            premium: StateStyle::new(Some(Color::Yellow), false, false, None),
+            unknown: StateStyle::new(Some(Color::DarkGrey), false, false, None),
+        }
+    }
+}
+
+impl Theme {
+    /// Look up one of the built-in themes by name
+    pub fn preset(name: &str) -> Result<Self, ThemeError> {
+        match name.to_lowercase().as_str() {
+            "default" => Ok(Theme::default()),
+            // Blue/orange stays distinguishable for the common forms of colour blindness,
+            // and the glyphs carry the state on their own
+            "colorblind" => Ok(Self {
+                available: StateStyle::new(Some(Color::Blue), true, false, Some("+")),
+                taken: StateStyle::new(Some(Color::DarkYellow), false, false, Some("x")),
+                premium: StateStyle::new(Some(Color::White), false, true, Some("*")),
+                unknown: StateStyle::new(Some(Color::DarkGrey), false, false, Some("?")),
+            }),
+            "monochrome" => Ok(Self {
+                available: StateStyle::new(None, true, false, Some("+")),
+                taken: StateStyle::new(None, false, false, Some("x")),
+                premium: StateStyle::new(None, false, true, Some("*")),
+                unknown: StateStyle::new(None, false, false, Some("?")),
+            }),
+            _ => Err(ThemeError::UnknownPreset(name.to_string())),
+        }
+    }
+
+    /// Build the theme to use, preferring a preset given on the command line over the
+    /// config file's theme section
+    pub fn resolve(config: Option<&ThemeConfig>, preset: Option<&str>) -> Result<Self, ThemeError> {
+        if let Some(preset) = preset {
+            return Theme::preset(preset);
+        }
+
+        let Some(config) = config else {
+            return Ok(Theme::default());
+        };
+
+        let mut theme = match &config.preset {
+            Some(preset) => Theme::preset(preset)?,
+            None => Theme::default(),
+        };
+
+        for (style, overrides) in [
+            (&mut theme.available, &config.available),
+            (&mut theme.taken, &config.taken),
+            (&mut theme.premium, &config.premium),
+            (&mut theme.unknown, &config.unknown),
+        ] {
+            if let Some(overrides) = overrides {
+                style.apply(overrides)?;
+            }
+        }
+
+        Ok(theme)
+    }
+
+    pub fn style(&self, status: DomainStatus) -> &StateStyle {
+        match status {
+            DomainStatus::Available => &self.available,
+            DomainStatus::Taken => &self.taken,
+            DomainStatus::Premium => &self.premium,
+            DomainStatus::Unknown => &self.unknown,
+        }
+    }
+}
+
+/// Parse a colour name as understood by crossterm (e.g. `red`, `dark_blue`) or a `#rrggbb` hex value.
+/// `none` leaves the terminal's default colour in place.
+pub fn parse_color(value: &str) -> Result<Option<Color>, ThemeError> {
+    if value.eq_ignore_ascii_case("none") {
+        return Ok(None);
+    }
+
+    if let Some(hex) = value.strip_prefix('#') {
+        if let (6, Ok(rgb)) = (hex.len(), u32::from_str_radix(hex, 16)) {
+            return Ok(Some(Color::Rgb {
+                r: (rgb >> 16) as u8,
+                g: (rgb >> 8) as u8,
+                b: rgb as u8,
+            }));
+        }
+        return Err(ThemeError::UnknownColor(value.to_string()));
+    }
+
+    Color::try_from(value)
+        .map(Some)
+        .map_err(|_| ThemeError::UnknownColor(value.to_string()))
+}
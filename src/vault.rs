@@ -0,0 +1,268 @@
+use crate::config::write_private_file;
+use argon2::Argon2;
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use chacha20poly1305::aead::rand_core::RngCore;
+use chacha20poly1305::aead::{Aead, AeadCore, KeyInit, OsRng};
+use chacha20poly1305::{Key, XChaCha20Poly1305, XNonce};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::env;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+use zeroize::Zeroizing;
+
+pub const PASSPHRASE_ENV: &str = "NAMEKIT_PASSPHRASE";
+
+const VAULT_VERSION: u32 = 1;
+const SALT_LEN: usize = 16;
+
+#[derive(Debug)]
+pub enum VaultError {
+    IoError(io::Error),
+    JsonError(serde_json::Error),
+    Corrupt,
+    WrongPassphrase,
+    EmptyPassphrase,
+    PassphraseMismatch,
+    /// There is no terminal to prompt on and `NAMEKIT_PASSPHRASE` isn't set
+    NoPassphrase(io::Error),
+}
+
+impl fmt::Display for VaultError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            VaultError::IoError(e) => write!(f, "IO error: {}", e),
+            VaultError::JsonError(e) => write!(f, "JSON error: {}", e),
+            VaultError::Corrupt => write!(f, "Credential vault is corrupt"),
+            VaultError::WrongPassphrase => write!(f, "Incorrect vault passphrase"),
+            VaultError::EmptyPassphrase => write!(f, "Vault passphrase must not be empty"),
+            VaultError::PassphraseMismatch => write!(f, "Passphrases do not match"),
+            VaultError::NoPassphrase(e) => write!(
+                f,
+                "Could not prompt for the vault passphrase ({}). Set {} to supply it without a terminal",
+                e, PASSPHRASE_ENV
+            ),
+        }
+    }
+}
+
+impl From<io::Error> for VaultError {
+    fn from(err: io::Error) -> Self {
+        VaultError::IoError(err)
+    }
+}
+
+impl From<serde_json::Error> for VaultError {
+    fn from(err: serde_json::Error) -> Self {
+        VaultError::JsonError(err)
+    }
+}
+
+impl std::error::Error for VaultError {}
+
+/// On-disk layout of the credential vault. The ciphertext is a JSON map of profile name
+/// to API token, encrypted with XChaCha20-Poly1305 under a key derived from the
+/// passphrase with Argon2id.
+#[derive(Serialize, Deserialize)]
+struct VaultFile {
+    version: u32,
+    salt: String,
+    nonce: String,
+    ciphertext: String,
+}
+
+/// Get the vault passphrase from `NAMEKIT_PASSPHRASE`, or prompt for it without echoing.
+/// When `confirm` is set (creating a new vault) the prompt asks twice.
+pub fn passphrase(confirm: bool) -> Result<Zeroizing<String>, VaultError> {
+    if let Some(passphrase) = env::var(PASSPHRASE_ENV).ok().filter(|p| !p.is_empty()) {
+        return Ok(Zeroizing::new(passphrase));
+    }
+
+    let prompt = |text| rpassword::prompt_password(text).map_err(VaultError::NoPassphrase);
+    let passphrase = Zeroizing::new(prompt("Vault passphrase: ")?);
+    if passphrase.is_empty() {
+        return Err(VaultError::EmptyPassphrase);
+    }
+
+    if confirm {
+        let again = Zeroizing::new(prompt("Confirm passphrase: ")?);
+        if *again != *passphrase {
+            return Err(VaultError::PassphraseMismatch);
+        }
+    }
+
+    Ok(passphrase)
+}
+
+fn derive_key(passphrase: &str, salt: &[u8]) -> Result<Zeroizing<[u8; 32]>, VaultError> {
+    let mut key = Zeroizing::new([0u8; 32]);
+    Argon2::default()
+        .hash_password_into(passphrase.as_bytes(), salt, key.as_mut())
+        .map_err(|_| VaultError::Corrupt)?;
+    Ok(key)
+}
+
+/// Decrypt the tokens stored in the vault at `path`, or an empty map if there is no vault yet
+pub fn read_tokens(path: &Path, passphrase: &str) -> Result<BTreeMap<String, String>, VaultError> {
+    if !path.exists() {
+        return Ok(BTreeMap::new());
+    }
+
+    let vault: VaultFile = serde_json::from_str(&fs::read_to_string(path)?)?;
+    if vault.version != VAULT_VERSION {
+        return Err(VaultError::Corrupt);
+    }
+
+    let decode = |value: &str| BASE64.decode(value).map_err(|_| VaultError::Corrupt);
+    let salt = decode(&vault.salt)?;
+    let nonce = decode(&vault.nonce)?;
+    let ciphertext = decode(&vault.ciphertext)?;
+    if nonce.len() != 24 {
+        return Err(VaultError::Corrupt);
+    }
+
+    let key = derive_key(passphrase, &salt)?;
+    let cipher = XChaCha20Poly1305::new(Key::from_slice(key.as_ref()));
+
+    // Authentication failure means the passphrase was wrong (or the file was tampered with)
+    let plaintext = Zeroizing::new(
+        cipher
+            .decrypt(XNonce::from_slice(&nonce), ciphertext.as_ref())
+            .map_err(|_| VaultError::WrongPassphrase)?,
+    );
+
+    Ok(serde_json::from_slice(&plaintext)?)
+}
+
+/// Encrypt `tokens` with a fresh salt and nonce and replace the vault at `path`
+pub fn write_tokens(
+    path: &Path,
+    tokens: &BTreeMap<String, String>,
+    passphrase: &str,
+) -> Result<(), VaultError> {
+    let mut salt = [0u8; SALT_LEN];
+    OsRng.fill_bytes(&mut salt);
+
+    let key = derive_key(passphrase, &salt)?;
+    let cipher = XChaCha20Poly1305::new(Key::from_slice(key.as_ref()));
+    let nonce = XChaCha20Poly1305::generate_nonce(&mut OsRng);
+
+    let plaintext = Zeroizing::new(serde_json::to_vec(tokens)?);
+    let ciphertext = cipher
+        .encrypt(&nonce, plaintext.as_ref())
+        .map_err(|_| VaultError::Corrupt)?;
+
+    let vault = VaultFile {
+        version: VAULT_VERSION,
+        salt: BASE64.encode(salt),
+        nonce: BASE64.encode(nonce),
+        ciphertext: BASE64.encode(ciphertext),
+    };
+
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    write_private_file(path, serde_json::to_string_pretty(&vault)?.as_bytes())?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    /// A vault path of its own for each test, as the tests run in parallel
+    fn vault_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("namekit-vault-{}-{}.enc", std::process::id(), name))
+    }
+
+    fn sample_tokens() -> BTreeMap<String, String> {
+        BTreeMap::from([
+            ("default".to_string(), "token-abcdefghij".to_string()),
+            ("staging".to_string(), "token-0123456789".to_string()),
+        ])
+    }
+
+    /// Write a vault, let `tamper` change its file contents, then read it back
+    fn read_tampered(
+        name: &str,
+        tamper: impl FnOnce(&mut VaultFile),
+    ) -> Result<BTreeMap<String, String>, VaultError> {
+        let path = vault_path(name);
+        write_tokens(&path, &sample_tokens(), "passphrase").unwrap();
+        let mut vault: VaultFile =
+            serde_json::from_str(&fs::read_to_string(&path).unwrap()).unwrap();
+        tamper(&mut vault);
+        fs::write(&path, serde_json::to_string(&vault).unwrap()).unwrap();
+
+        let result = read_tokens(&path, "passphrase");
+        fs::remove_file(&path).unwrap();
+        result
+    }
+
+    #[test]
+    fn tokens_round_trip() {
+        let path = vault_path("round-trip");
+        write_tokens(&path, &sample_tokens(), "correct horse").unwrap();
+
+        let contents = fs::read_to_string(&path).unwrap();
+        assert!(!contents.contains("token-abcdefghij"));
+        assert_eq!(
+            read_tokens(&path, "correct horse").unwrap(),
+            sample_tokens()
+        );
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn a_missing_vault_holds_no_tokens() {
+        assert!(
+            read_tokens(&vault_path("missing"), "anything")
+                .unwrap()
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn rejects_the_wrong_passphrase() {
+        let path = vault_path("wrong-passphrase");
+        write_tokens(&path, &sample_tokens(), "correct horse").unwrap();
+        assert!(matches!(
+            read_tokens(&path, "battery staple"),
+            Err(VaultError::WrongPassphrase)
+        ));
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn detects_a_changed_ciphertext() {
+        let result = read_tampered("changed-ciphertext", |vault| {
+            let mut ciphertext = BASE64.decode(&vault.ciphertext).unwrap();
+            ciphertext[0] ^= 1;
+            vault.ciphertext = BASE64.encode(ciphertext);
+        });
+        assert!(matches!(result, Err(VaultError::WrongPassphrase)));
+    }
+
+    #[test]
+    fn rejects_a_malformed_vault() {
+        let short_nonce = read_tampered("short-nonce", |vault| {
+            vault.nonce = BASE64.encode([0u8; 12]);
+        });
+        assert!(matches!(short_nonce, Err(VaultError::Corrupt)));
+
+        let newer = read_tampered("newer-version", |vault| {
+            vault.version = VAULT_VERSION + 1;
+        });
+        assert!(matches!(newer, Err(VaultError::Corrupt)));
+
+        let not_base64 = read_tampered("not-base64", |vault| {
+            vault.salt = "not base64!".to_string();
+        });
+        assert!(matches!(not_base64, Err(VaultError::Corrupt)));
+    }
+}
@@ -0,0 +1,524 @@
+//! End-to-end tests running the namekit binary against a mock of the API
+
+mod support;
+
+use support::{MockServer, Response, TOKEN, TestEnv, stderr, stdout};
+
+const FOO_COM: &str = r#"{"domain": "foo.com", "available": true, "premium": false}"#;
+const FOO_DEV: &str = r#"{"domain": "foo.dev", "available": false, "premium": false}"#;
+const FOO_APP: &str = r#"{"domain": "foo.app", "available": true, "premium": true}"#;
+
+/// Search flags giving plain, stable output: no colour and no summary line
+const LIST: [&str; 5] = ["--color", "never", "--quiet", "--output", "list"];
+
+fn search(env: &TestEnv, flags: &[&str], query: &str) -> std::process::Output {
+    let mut args = LIST.to_vec();
+    args.extend_from_slice(flags);
+    args.extend_from_slice(&["search", "tld", query]);
+    env.run(&args)
+}
+
+#[test]
+fn sends_the_search_and_shows_results_split_across_chunks() {
+    let server = MockServer::start(&[(
+        "/domains/stream",
+        Response::stream()
+            .chunk(r#"{"domain": "foo.com", "avail"#)
+            .delay(50)
+            .chunk("able\": true}\n{\"domain\": \"caf")
+            // Split inside the two bytes of "é"
+            .bytes(&[0xc3])
+            .bytes(b"\xa9.com\", \"available\": true}\n")
+            .line(FOO_DEV)
+            .chunk(FOO_APP),
+    )]);
+    let env = TestEnv::new("chunks", &server);
+
+    let output = search(&env, &["--show-taken"], "foo");
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(
+        stdout(&output),
+        "foo.com\ncaf\u{e9}.com\nfoo.dev x\nfoo.app *\n"
+    );
+
+    let requests = server.requests();
+    assert_eq!(requests.len(), 1);
+    let request = &requests[0];
+    assert_eq!(request.method, "POST");
+    assert_eq!(request.path, "/domains/stream");
+    assert_eq!(
+        request.headers["authorization"],
+        format!("Bearer {}", TOKEN)
+    );
+    assert_eq!(
+        request.json(),
+        serde_json::json!({"q": "foo", "mode": "tld", "tlds": "com,dev,app"})
+    );
+}
+
+#[test]
+fn skips_malformed_lines_and_keeps_going() {
+    let server = MockServer::start(&[(
+        "/domains/stream",
+        Response::stream()
+            .line(FOO_COM)
+            .line(r#"{"domain": "trunc"#)
+            .chunk("\n\r\n")
+            .line(r#"{"event": "progress"}"#)
+            .line(FOO_APP),
+    )]);
+    let env = TestEnv::new("malformed", &server);
+
+    let output = search(&env, &[], "foo");
+    assert!(output.status.success());
+    assert_eq!(stdout(&output), "foo.com\nfoo.app *\n");
+    let errors = stderr(&output);
+    assert!(errors.contains("Error parsing JSON line"), "{}", errors);
+    assert!(errors.contains("Ignoring unexpected JSON"), "{}", errors);
+}
+
+#[test]
+fn json_output_is_a_valid_array() {
+    let server = MockServer::start(&[(
+        "/domains/stream",
+        Response::stream().line(FOO_COM).line(FOO_DEV).line(FOO_APP),
+    )]);
+    let env = TestEnv::new("json", &server);
+
+    let output = env.run(&["--output", "json", "search", "tld", "foo"]);
+    assert!(output.status.success());
+    let results: serde_json::Value = serde_json::from_str(&stdout(&output)).unwrap();
+    let names: Vec<&str> = results
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|result| result["name"].as_str().unwrap())
+        .collect();
+    assert_eq!(names, ["foo.com", "foo.app"]);
+
+    // The summary goes to stderr so stdout stays valid JSON
+    assert!(stderr(&output).contains("3 checked"));
+}
+
+#[test]
+fn reports_a_rejected_token() {
+    let server = MockServer::start(&[("/domains/stream", Response::status(401))]);
+    let env = TestEnv::new("unauthorized", &server);
+
+    let output = search(&env, &[], "foo");
+    assert_eq!(stdout(&output), "");
+    assert!(stderr(&output).contains("401 Unauthorized"));
+}
+
+#[test]
+fn reports_when_the_rate_limit_resets() {
+    let server = MockServer::start(&[(
+        "/domains/stream",
+        Response::status(429).header("Retry-After", "3600"),
+    )]);
+    let env = TestEnv::new("rate-limited", &server);
+
+    let output = search(&env, &[], "foo");
+    let errors = stderr(&output);
+    assert!(errors.contains("reached the limit"), "{}", errors);
+    assert!(errors.contains("resets at"), "{}", errors);
+}
+
+#[test]
+fn reports_server_errors() {
+    let server = MockServer::start(&[("/domains/stream", Response::status(500))]);
+    let env = TestEnv::new("server-error", &server);
+
+    let output = search(&env, &[], "foo");
+    assert_eq!(stdout(&output), "");
+    assert!(stderr(&output).contains("500 Internal Server Error"));
+}
+
+#[test]
+fn shows_partial_results_when_the_stream_stalls() {
+    let server = MockServer::start(&[(
+        "/domains/stream",
+        Response::stream().line(FOO_COM).delay(5000).line(FOO_APP),
+    )]);
+    let env = TestEnv::new("stalled", &server);
+
+    let output = search(&env, &["--idle-timeout", "1"], "foo");
+    assert_eq!(stdout(&output), "foo.com\n");
+    let errors = stderr(&output);
+    assert!(errors.contains("stalled after 1 results"), "{}", errors);
+}
+
+#[test]
+fn reports_a_server_that_never_answers() {
+    let server = MockServer::start(&[(
+        "/domains/stream",
+        Response::stream().delay_headers(5000).line(FOO_COM),
+    )]);
+    let env = TestEnv::new("silent", &server);
+
+    let output = search(&env, &["--idle-timeout", "1"], "foo");
+    assert_eq!(stdout(&output), "");
+    let errors = stderr(&output);
+    assert!(errors.contains("never answered"), "{}", errors);
+}
+
+#[test]
+fn login_saves_a_token_only_once_the_api_accepts_it() {
+    let accepting = MockServer::start(&[(
+        "/account",
+        Response::json(r#"{"email": "dev@example.com", "plan": "pro"}"#),
+    )]);
+    let env = TestEnv::new("login", &accepting);
+
+    let output = env.run(&["--token", "new-token-abcdefghij", "login"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(stdout(&output).contains("as dev@example.com (pro plan)"));
+    let config = std::fs::read_to_string(env.config_path()).unwrap();
+    assert!(config.contains("new-token-abcdefghij"));
+
+    let rejecting = MockServer::start(&[("/account", Response::status(401))]);
+    let output = env.run(&[
+        "--api-server",
+        &rejecting.url,
+        "--token",
+        "bad-token-abcdefghij",
+        "login",
+    ]);
+    assert_eq!(output.status.code(), Some(1));
+    assert!(stderr(&output).contains("was not changed"));
+    let config = std::fs::read_to_string(env.config_path()).unwrap();
+    assert!(config.contains("new-token-abcdefghij"));
+    assert!(!config.contains("bad-token"));
+}
+
+#[cfg(unix)]
+#[test]
+fn ctrl_c_keeps_partial_results_and_closes_the_json_file() {
+    use std::io::{BufRead, BufReader, Read};
+    use std::process::{Command, Stdio};
+
+    let server = MockServer::start(&[(
+        "/domains/stream",
+        Response::stream().line(FOO_COM).delay(10_000).line(FOO_APP),
+    )]);
+    let env = TestEnv::new("interrupted", &server);
+    let json = env.path("results.json");
+
+    let mut args = LIST.to_vec();
+    args.extend_from_slice(&["--out", json.to_str().unwrap(), "search", "tld", "foo"]);
+    let mut child = env
+        .command(&args)
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+
+    // Interrupt once the first result is on screen
+    let mut out = BufReader::new(child.stdout.take().unwrap());
+    let mut first = String::new();
+    out.read_line(&mut first).unwrap();
+    assert_eq!(first, "foo.com\n");
+    let status = Command::new("kill")
+        .args(["-INT", &child.id().to_string()])
+        .status()
+        .unwrap();
+    assert!(status.success());
+
+    let status = child.wait().unwrap();
+    let mut rest = String::new();
+    out.read_to_string(&mut rest).unwrap();
+    let mut errors = String::new();
+    child
+        .stderr
+        .take()
+        .unwrap()
+        .read_to_string(&mut errors)
+        .unwrap();
+
+    assert_eq!(status.code(), Some(130), "{}", errors);
+    assert_eq!(rest, "");
+    assert!(errors.contains("Search interrupted"));
+    let saved: serde_json::Value =
+        serde_json::from_str(&std::fs::read_to_string(&json).unwrap()).unwrap();
+    assert_eq!(saved[0]["name"], "foo.com");
+}
+
+#[test]
+fn runs_each_query_and_labels_its_results() {
+    let server = MockServer::start(&[(
+        "/domains/stream",
+        Response::stream().line(FOO_COM).delay(50).line(FOO_APP),
+    )]);
+    let env = TestEnv::new("several-queries", &server);
+
+    let output = env.run(&["--output", "json", "search", "tld", "foo", "bar"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    let results: serde_json::Value = serde_json::from_str(&stdout(&output)).unwrap();
+    let mut labelled: Vec<(&str, &str)> = results
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|result| {
+            (
+                result["query"].as_str().unwrap(),
+                result["name"].as_str().unwrap(),
+            )
+        })
+        .collect();
+    labelled.sort();
+    assert_eq!(
+        labelled,
+        [
+            ("bar", "foo.app"),
+            ("bar", "foo.com"),
+            ("foo", "foo.app"),
+            ("foo", "foo.com")
+        ]
+    );
+
+    let mut queries: Vec<String> = server
+        .requests()
+        .iter()
+        .map(|request| request.json()["q"].as_str().unwrap().to_string())
+        .collect();
+    queries.sort();
+    assert_eq!(queries, ["bar", "foo"]);
+
+    let output = search(&env, &["--sections"], "foo");
+    assert_eq!(stdout(&output), "foo.com\nfoo.app *\n");
+
+    let mut args = LIST.to_vec();
+    args.extend_from_slice(&["--sections", "search", "ai", "--each", "foo", "bar"]);
+    let output = env.run(&args);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(
+        stdout(&output),
+        "== foo ==\nfoo.com\nfoo.app *\n\n== bar ==\nfoo.com\nfoo.app *\n"
+    );
+
+    // Quoted prompts are searched separately without --each, unquoted words are joined
+    let mut args = LIST.to_vec();
+    args.extend_from_slice(&["--sections", "search", "ai", "coffee shop", "tea"]);
+    let output = env.run(&args);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(
+        stdout(&output),
+        "== coffee shop ==\nfoo.com\nfoo.app *\n\n== tea ==\nfoo.com\nfoo.app *\n"
+    );
+
+    let before = server.requests().len();
+    let mut args = LIST.to_vec();
+    args.extend_from_slice(&["search", "ai", "coffee", "shop"]);
+    let output = env.run(&args);
+    assert!(output.status.success(), "{}", stderr(&output));
+    let requests = server.requests();
+    assert_eq!(requests.len(), before + 1);
+    assert_eq!(requests[before].json()["q"], "coffee shop");
+
+    let record = env.path("session.ndjson");
+    let output = env.run(&[
+        "--record",
+        record.to_str().unwrap(),
+        "search",
+        "tld",
+        "foo",
+        "bar",
+    ]);
+    assert_eq!(output.status.code(), Some(2));
+    assert!(stderr(&output).contains("several queries"));
+}
+
+#[test]
+fn rejects_a_bad_theme_before_searching() {
+    let server = MockServer::start(&[("/domains/stream", Response::stream().line(FOO_COM))]);
+    let env = TestEnv::new("bad-theme", &server);
+    let mut config: serde_json::Value =
+        serde_json::from_str(&std::fs::read_to_string(env.config_path()).unwrap()).unwrap();
+    config["theme"] = serde_json::json!({"premium": {"color": "blu"}});
+    std::fs::write(env.config_path(), config.to_string()).unwrap();
+
+    let output = search(&env, &[], "foo");
+    assert_eq!(output.status.code(), Some(2));
+    let errors = stderr(&output);
+    assert!(errors.contains("Unknown theme colour 'blu'"), "{}", errors);
+    assert!(server.requests().is_empty());
+}
+
+#[test]
+fn logout_leaves_tokens_from_the_environment_alone() {
+    let server = MockServer::start(&[]);
+    let env = TestEnv::new("logout", &server);
+
+    let output = env.run(&["logout"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(stdout(&output).contains("Logged out of profile 'default'"));
+    let config = std::fs::read_to_string(env.config_path()).unwrap();
+    assert!(!config.contains(TOKEN));
+
+    let output = env
+        .command(&["logout"])
+        .env("NAMEKIT_TOKEN", TOKEN)
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{}", stderr(&output));
+    let out = stdout(&output);
+    assert!(
+        out.contains("No API token is saved for profile 'default'"),
+        "{}",
+        out
+    );
+    assert!(
+        out.contains("NAMEKIT_TOKEN environment variable"),
+        "{}",
+        out
+    );
+    assert!(!out.contains("Logged out"), "{}", out);
+    assert_eq!(std::fs::read_to_string(env.config_path()).unwrap(), config);
+}
+
+#[test]
+fn a_mistyped_profile_is_not_created_by_writes() {
+    let server = MockServer::start(&[]);
+    let env = TestEnv::new("mistyped-profile", &server);
+    let config = std::fs::read_to_string(env.config_path()).unwrap();
+
+    for args in [
+        &[
+            "--profile",
+            "nope",
+            "config",
+            "set-token",
+            "abcdefghijklmnop",
+        ][..],
+        &[
+            "--profile",
+            "nope",
+            "config",
+            "set-api-server",
+            "https://example.com",
+        ],
+        &[
+            "--profile",
+            "nope",
+            "config",
+            "set",
+            "api_server",
+            "https://example.com",
+        ],
+        &["--profile", "nope", "config", "unset", "api_token"],
+    ] {
+        let output = env.run(args);
+        assert!(!output.status.success(), "{:?} succeeded", args);
+        assert!(
+            stderr(&output).contains("Profile 'nope' not found"),
+            "{}",
+            stderr(&output)
+        );
+    }
+    assert_eq!(std::fs::read_to_string(env.config_path()).unwrap(), config);
+
+    let output = env.run(&["config", "use", "nope"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    let output = env.run(&[
+        "--profile",
+        "nope",
+        "config",
+        "set-token",
+        "abcdefghijklmnop",
+    ]);
+    assert!(output.status.success(), "{}", stderr(&output));
+}
+
+#[test]
+fn piped_list_output_holds_only_domains() {
+    let server = MockServer::start(&[(
+        "/domains/stream",
+        Response::stream().line(FOO_COM).line(FOO_DEV).line(FOO_APP),
+    )]);
+    let env = TestEnv::new("piped-list", &server);
+
+    let output = env.run(&[
+        "--output", "list", "--color", "never", "search", "tld", "foo",
+    ]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(stdout(&output), "foo.com\nfoo.app *\n");
+    assert!(stderr(&output).contains("3 checked"));
+}
+
+#[test]
+fn a_bad_out_path_is_reported_before_searching() {
+    let server = MockServer::start(&[("/domains/stream", Response::stream().line(FOO_COM))]);
+    let env = TestEnv::new("bad-out", &server);
+    let path = env.path("missing/results.json");
+
+    let output = search(&env, &["--out", path.to_str().unwrap()], "foo");
+    assert_eq!(output.status.code(), Some(2));
+    let errors = stderr(&output);
+    assert!(errors.contains("Could not create"), "{}", errors);
+    assert!(errors.contains("missing/results.json"), "{}", errors);
+    assert!(server.requests().is_empty());
+}
+
+#[test]
+fn recordings_leave_out_cookies() {
+    let server = MockServer::start(&[(
+        "/domains/stream",
+        Response::stream()
+            .header("Set-Cookie", "session=abc123")
+            .line(FOO_COM),
+    )]);
+    let env = TestEnv::new("record-cookies", &server);
+    let record = env.path("session.ndjson");
+
+    let output = search(&env, &["--record", record.to_str().unwrap()], "foo");
+    assert!(output.status.success(), "{}", stderr(&output));
+    let recording = std::fs::read_to_string(&record).unwrap();
+    assert!(!recording.contains("abc123"), "{}", recording);
+    assert!(
+        recording.contains(r#""set-cookie":"<redacted>""#),
+        "{}",
+        recording
+    );
+    assert!(!recording.contains(TOKEN));
+}
+
+#[test]
+fn recordings_keep_lines_that_could_not_be_read() {
+    let body = [
+        FOO_COM.as_bytes(),
+        b"\n\n{\"domain\": \"caf\xe9.dev\"}\n",
+        FOO_APP.as_bytes(),
+        b"\n",
+    ]
+    .concat();
+    let server = MockServer::start(&[("/domains/stream", Response::stream().bytes(&body))]);
+    let env = TestEnv::new("record-raw", &server);
+    let record = env.path("session.ndjson");
+
+    let live = search(&env, &["--record", record.to_str().unwrap()], "foo");
+    assert!(live.status.success(), "{}", stderr(&live));
+    let recording = std::fs::read(&record).unwrap();
+    assert!(recording.ends_with(&body));
+
+    let mut args = LIST.to_vec();
+    args.extend_from_slice(&["--replay", record.to_str().unwrap()]);
+    let replayed = env.run(&args);
+    assert!(replayed.status.success(), "{}", stderr(&replayed));
+    assert_eq!(stdout(&replayed), stdout(&live));
+    assert!(stderr(&replayed).contains("Skipping response line: line is not valid UTF-8"));
+}
+
+#[test]
+fn templates_show_the_price() {
+    let server = MockServer::start(&[(
+        "/domains/stream",
+        Response::stream()
+            .line(r#"{"domain": "foo.com", "available": true, "premium": false, "price": 12.99}"#)
+            .line(FOO_APP),
+    )]);
+    let env = TestEnv::new("template-price", &server);
+
+    let output = search(&env, &["--format", r"{name}\t{tld}\t{price}"], "foo");
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(stdout(&output), "foo.com\tcom\t12.99\nfoo.app\tapp\t\n");
+}
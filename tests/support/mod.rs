@@ -0,0 +1,306 @@
+//! Helpers for running the namekit binary end-to-end against a scripted mock of the API
+
+use std::collections::HashMap;
+use std::fs;
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::path::PathBuf;
+use std::process::{Command, Output};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+/// Token the test config is set up with
+pub const TOKEN: &str = "test-token-0123456789";
+
+/// One piece of a scripted response body
+#[derive(Clone)]
+enum Step {
+    /// Bytes sent as a single HTTP chunk, which may end part way through a line
+    Chunk(Vec<u8>),
+    Delay(Duration),
+}
+
+/// What the mock sends back for a route
+#[derive(Clone)]
+pub struct Response {
+    status: u16,
+    headers: Vec<(String, String)>,
+    /// How long to wait before sending the status line and headers
+    head_delay: Duration,
+    steps: Vec<Step>,
+}
+
+impl Response {
+    /// A streamed NDJSON response, built up with `line`, `chunk` and `delay`
+    pub fn stream() -> Self {
+        Self::status(200).header("Content-Type", "application/x-ndjson")
+    }
+
+    /// A response with no body, e.g. `Response::status(401)`
+    pub fn status(status: u16) -> Self {
+        Self {
+            status,
+            headers: Vec::new(),
+            head_delay: Duration::ZERO,
+            steps: Vec::new(),
+        }
+    }
+
+    /// A JSON document sent in one chunk
+    pub fn json(body: &str) -> Self {
+        Self::status(200)
+            .header("Content-Type", "application/json")
+            .chunk(body)
+    }
+
+    pub fn header(mut self, name: &str, value: &str) -> Self {
+        self.headers.push((name.to_string(), value.to_string()));
+        self
+    }
+
+    /// One complete line, sent as its own chunk
+    pub fn line(self, line: &str) -> Self {
+        self.chunk(&format!("{}\n", line))
+    }
+
+    /// Raw text sent as one chunk, with no newline added
+    pub fn chunk(self, text: &str) -> Self {
+        self.bytes(text.as_bytes())
+    }
+
+    /// Raw bytes sent as one chunk, for splitting inside a multi-byte character
+    pub fn bytes(mut self, bytes: &[u8]) -> Self {
+        self.steps.push(Step::Chunk(bytes.to_vec()));
+        self
+    }
+
+    /// Pause before answering at all, like a server that accepts the connection and then hangs
+    pub fn delay_headers(mut self, millis: u64) -> Self {
+        self.head_delay = Duration::from_millis(millis);
+        self
+    }
+
+    /// Pause before sending the next chunk
+    pub fn delay(mut self, millis: u64) -> Self {
+        self.steps.push(Step::Delay(Duration::from_millis(millis)));
+        self
+    }
+}
+
+/// A request the mock received
+#[derive(Clone, Debug)]
+pub struct Request {
+    pub method: String,
+    pub path: String,
+    pub headers: HashMap<String, String>,
+    pub body: String,
+}
+
+impl Request {
+    pub fn json(&self) -> serde_json::Value {
+        serde_json::from_str(&self.body).expect("request body is JSON")
+    }
+}
+
+/// An HTTP server on a free local port answering each path with a scripted response.
+/// Paths without a route get a 404.
+pub struct MockServer {
+    pub url: String,
+    requests: Arc<Mutex<Vec<Request>>>,
+}
+
+impl MockServer {
+    pub fn start(routes: &[(&str, Response)]) -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").expect("bind mock server");
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let routes: Arc<HashMap<String, Response>> = Arc::new(
+            routes
+                .iter()
+                .map(|(path, response)| (path.to_string(), response.clone()))
+                .collect(),
+        );
+        let requests = Arc::new(Mutex::new(Vec::new()));
+
+        let log = Arc::clone(&requests);
+        thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                let routes = Arc::clone(&routes);
+                let log = Arc::clone(&log);
+                thread::spawn(move || {
+                    // The client may hang up early, e.g. after a timeout; that's not an error here
+                    let _ = serve(stream, &routes, &log);
+                });
+            }
+        });
+
+        Self { url, requests }
+    }
+
+    /// Every request received so far, oldest first
+    pub fn requests(&self) -> Vec<Request> {
+        self.requests.lock().unwrap().clone()
+    }
+}
+
+fn serve(
+    stream: TcpStream,
+    routes: &HashMap<String, Response>,
+    log: &Mutex<Vec<Request>>,
+) -> io::Result<()> {
+    let mut reader = BufReader::new(stream.try_clone()?);
+    let request = read_request(&mut reader)?;
+    let response = routes
+        .get(&request.path)
+        .cloned()
+        .unwrap_or_else(|| Response::status(404));
+    log.lock().unwrap().push(request);
+
+    thread::sleep(response.head_delay);
+    let mut out = stream;
+    write!(
+        out,
+        "HTTP/1.1 {} {}\r\n",
+        response.status,
+        reason(response.status)
+    )?;
+    for (name, value) in &response.headers {
+        write!(out, "{}: {}\r\n", name, value)?;
+    }
+    write!(
+        out,
+        "Transfer-Encoding: chunked\r\nConnection: close\r\n\r\n"
+    )?;
+    out.flush()?;
+
+    for step in &response.steps {
+        match step {
+            Step::Chunk(bytes) => {
+                write!(out, "{:x}\r\n", bytes.len())?;
+                out.write_all(bytes)?;
+                write!(out, "\r\n")?;
+                out.flush()?;
+            }
+            Step::Delay(duration) => thread::sleep(*duration),
+        }
+    }
+    write!(out, "0\r\n\r\n")?;
+    out.flush()
+}
+
+fn read_request(reader: &mut impl BufRead) -> io::Result<Request> {
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line)?;
+    let mut parts = request_line.split_whitespace();
+    let method = parts.next().unwrap_or_default().to_string();
+    let path = parts.next().unwrap_or_default().to_string();
+
+    let mut headers = HashMap::new();
+    loop {
+        let mut line = String::new();
+        reader.read_line(&mut line)?;
+        let line = line.trim_end();
+        if line.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = line.split_once(':') {
+            headers.insert(name.trim().to_lowercase(), value.trim().to_string());
+        }
+    }
+
+    let length = headers
+        .get("content-length")
+        .and_then(|length| length.parse().ok())
+        .unwrap_or(0);
+    let mut body = vec![0; length];
+    reader.read_exact(&mut body)?;
+
+    Ok(Request {
+        method,
+        path,
+        headers,
+        body: String::from_utf8_lossy(&body).into_owned(),
+    })
+}
+
+fn reason(status: u16) -> &'static str {
+    match status {
+        200 => "OK",
+        401 => "Unauthorized",
+        404 => "Not Found",
+        429 => "Too Many Requests",
+        500 => "Internal Server Error",
+        _ => "Unknown",
+    }
+}
+
+/// A config directory of its own for one test, pointed at `server` with `TOKEN`, and
+/// removed when dropped
+pub struct TestEnv {
+    dir: PathBuf,
+}
+
+impl TestEnv {
+    pub fn new(name: &str, server: &MockServer) -> Self {
+        let dir = std::env::temp_dir().join(format!("namekit-it-{}-{}", std::process::id(), name));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let env = Self { dir };
+
+        let output = env.run(&["config", "set-api-server", &server.url]);
+        assert!(output.status.success(), "{}", stderr(&output));
+        let output = env.run(&["config", "set-token", TOKEN]);
+        assert!(output.status.success(), "{}", stderr(&output));
+        env
+    }
+
+    pub fn config_path(&self) -> PathBuf {
+        self.dir.join("config.json")
+    }
+
+    /// A `namekit` command using this environment's config and nothing from the caller's
+    pub fn command(&self, args: &[&str]) -> Command {
+        let mut command = Command::new(env!("CARGO_BIN_EXE_namekit"));
+        command
+            .args(args)
+            .env("NAMEKIT_CONFIG", self.config_path())
+            .env_remove("NAMEKIT_TOKEN")
+            .env_remove("NAMEKIT_API_SERVER")
+            .env_remove("NAMEKIT_PASSPHRASE")
+            .env_remove("NO_COLOR")
+            .env_remove("CLICOLOR_FORCE");
+        for var in [
+            "HTTPS_PROXY",
+            "https_proxy",
+            "HTTP_PROXY",
+            "http_proxy",
+            "ALL_PROXY",
+            "all_proxy",
+        ] {
+            command.env_remove(var);
+        }
+        command
+    }
+
+    pub fn run(&self, args: &[&str]) -> Output {
+        self.command(args).output().expect("run namekit")
+    }
+
+    pub fn path(&self, name: &str) -> PathBuf {
+        self.dir.join(name)
+    }
+}
+
+impl Drop for TestEnv {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.dir);
+    }
+}
+
+pub fn stdout(output: &Output) -> String {
+    String::from_utf8_lossy(&output.stdout).into_owned()
+}
+
+pub fn stderr(output: &Output) -> String {
+    String::from_utf8_lossy(&output.stderr).into_owned()
+}